@@ -0,0 +1,134 @@
+// Box Bopper: Sokoban-like game
+// Copyright David Atkinson 2020-2021
+//
+// analyze.rs: check a level for things that make it unsolvable, or that look like mistakes
+
+use std::fmt;
+
+use crate::level::Level;
+use crate::vector::{Vector,ALLMOVES};
+use crate::deadlock::find_immovable_boxxes;
+use super::Obj;
+
+#[derive(Clone,PartialEq,Debug)]
+pub struct LevelReport {
+	pub num_boxxes: usize,
+	pub num_holes: usize,
+	pub num_reachable: usize,					// cells the human can get to, if the boxxes were out of the way
+	pub dead_boxxes: Vec::<Vector>,				// boxxes that start on a dead square
+	pub unreachable_boxxes: Vec::<Vector>,		// boxxes (not already in a hole) the human can't get to
+	pub unreachable_holes: Vec::<Vector>,		// empty holes the human can't get to
+	pub immovable_boxxes: Vec::<Vector>,		// boxxes (not already in a hole) that can never be pushed
+	pub floor_regions: Vec::<Vec::<Vector>>,	// areas of floor cut off from the human
+	pub human_boxed_in: bool,					// the human can't push any boxx to start with
+}
+
+impl LevelReport {
+	pub fn is_solvable(&self) -> bool {
+		// as far as we can tell without solving it
+		self.dead_boxxes.is_empty() && self.unreachable_boxxes.is_empty() && self.unreachable_holes.is_empty()
+			&& self.immovable_boxxes.is_empty() && !self.human_boxed_in && self.num_boxxes == self.num_holes
+	}
+	pub fn is_clean(&self) -> bool {
+		// solvable, with nothing odd about it either
+		self.is_solvable() && self.floor_regions.is_empty()
+	}
+}
+
+fn pts_to_string(pts: &[Vector]) -> String {
+	pts.iter().map(|p| p.to_string()).collect::<Vec::<String>>().join(" ")
+}
+
+impl fmt::Display for LevelReport {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "boxes: {}, holes: {}, reachable cells: {}", self.num_boxxes, self.num_holes, self.num_reachable)?;
+		if !self.dead_boxxes.is_empty() {
+			writeln!(f, "boxes on dead squares: {}", pts_to_string(&self.dead_boxxes))?;
+		}
+		if !self.unreachable_boxxes.is_empty() {
+			writeln!(f, "boxes the human can't get to: {}", pts_to_string(&self.unreachable_boxxes))?;
+		}
+		if !self.unreachable_holes.is_empty() {
+			writeln!(f, "holes the human can't get to: {}", pts_to_string(&self.unreachable_holes))?;
+		}
+		if !self.immovable_boxxes.is_empty() {
+			writeln!(f, "boxes that can never move: {}", pts_to_string(&self.immovable_boxxes))?;
+		}
+		for region in self.floor_regions.iter() {
+			writeln!(f, "floor cut off from the human: {}", pts_to_string(region))?;
+		}
+		if self.human_boxed_in {
+			writeln!(f, "human is boxed in")?;
+		}
+		Ok(())
+	}
+}
+
+impl Level {
+	pub fn analyze(&self) -> LevelReport {
+		let w = self.w as i32;
+		let h = self.h as i32;
+		let reach = self.reachable(&self.human_pos, true);
+		let mut report = LevelReport {
+			num_boxxes: 0,
+			num_holes: 0,
+			num_reachable: reach.iter().filter(|r| **r).count(),
+			dead_boxxes: Vec::new(),
+			unreachable_boxxes: Vec::new(),
+			unreachable_holes: Vec::new(),
+			immovable_boxxes: find_immovable_boxxes(self).into_iter().filter(|b| self.get_obj_at_pt(b) == Obj::Boxx).collect(),
+			floor_regions: Vec::new(),
+			human_boxed_in: false,
+		};
+
+		let mut seen = reach.clone();
+		for y in 0..h {
+			for x in 0..w {
+				let pt = Vector(x,y);
+				let obj = self.get_obj_at_pt(&pt);
+				let in_reach = reach[pt.to_index(self.w)];
+				if obj == Obj::Boxx || obj == Obj::BoxxInHole {
+					report.num_boxxes += 1;
+					if !in_reach && obj == Obj::Boxx {
+						report.unreachable_boxxes.push(pt);
+					}
+				}
+				if obj == Obj::Hole || obj == Obj::HumanInHole || obj == Obj::BoxxInHole {
+					report.num_holes += 1;
+					if !in_reach && obj == Obj::Hole {
+						report.unreachable_holes.push(pt);
+					}
+				}
+				if obj == Obj::Boxx && self.in_noboxx_pts(&pt) {
+					report.dead_boxxes.push(pt);
+				}
+				// each bit of floor we haven't been to yet starts a new region
+				if obj != Obj::Wall && !seen[pt.to_index(self.w)] {
+					let region_map = self.reachable(&pt, true);
+					let mut region = Vec::<Vector>::new();
+					for (i, r) in region_map.iter().enumerate() {
+						if *r {
+							seen[i] = true;
+							region.push(Vector(i as i32 % w, i as i32 / w));
+						}
+					}
+					report.floor_regions.push(region);
+				}
+			}
+		}
+
+		// can the human push anything from where they can walk to?
+		let walk = self.reachable(&self.human_pos, false);
+		let can_push = (0..walk.len()).filter(|i| walk[*i]).any(|i| {
+			let pt = Vector(i as i32 % w, i as i32 / w);
+			ALLMOVES.iter().any(|m| {
+				let b = pt.add_dir(m);
+				matches!(self.get_obj_at_pt_checked(&b), Obj::Boxx | Obj::BoxxInHole)
+					&& matches!(self.get_obj_at_pt_checked(&b.add_dir(m)), Obj::Space | Obj::Hole)
+			})
+		});
+		report.human_boxed_in = !can_push && !self.have_win_condition();
+
+		report
+	}
+}
@@ -21,7 +21,19 @@ pub mod vector;
 use vector::{Vector,Move,ALLMOVES};
 
 pub mod level;
-use level::{Level};
+use level::{Level,LevelParseError,ReplayError,string_to_moves};
+
+use std::fmt;
+
+pub mod collection;
+
+pub mod deadlock;
+use deadlock::{find_stuck_boxxes};
+
+pub mod analyze;
+
+pub mod event;
+use event::{GameEvent};
 
 pub mod dgens;
 //use dgens::{contains_only};
@@ -30,7 +42,7 @@ pub mod sprite;
 use sprite::{Sprite,Trans};
 
 pub mod time;
-use time::{get_time_ms};
+use time::{Clock,SystemClock,ManualClock};
 
 pub mod stackstack;
 
@@ -62,18 +74,107 @@ impl Obj {
 		}
 	}
 
-	pub fn from_char(c: &char) -> Obj {
+	pub fn from_char(c: &char) -> Option<Obj> {
 		return match c {
-			'#' => Obj::Wall,
-			' ' => Obj::Space,
-			'*' => Obj::Boxx,
-			'O' => Obj::Hole,
-			'&' => Obj::Human,
-			'%' => Obj::HumanInHole,
-			'@' => Obj::BoxxInHole,
-			_ => panic!("Char does not represent a valid object"),
+			'#' => Some(Obj::Wall),
+			' ' => Some(Obj::Space),
+			'*' => Some(Obj::Boxx),
+			'O' => Some(Obj::Hole),
+			'&' => Some(Obj::Human),
+			'%' => Some(Obj::HumanInHole),
+			'@' => Some(Obj::BoxxInHole),
+			_ => None,
 		};
 	}
+
+	// Standard XSB sokoban characters
+	pub fn to_xsb_char(&self) -> char {
+		match self {
+			Obj::Wall => '#',
+			Obj::Space => ' ',
+			Obj::Boxx => '$',
+			Obj::Hole => '.',
+			Obj::Human => '@',
+			Obj::HumanInHole => '+',
+			Obj::BoxxInHole => '*',
+		}
+	}
+
+	pub fn from_xsb_char(c: &char) -> Option<Obj> {
+		return match c {
+			'#' => Some(Obj::Wall),
+			' ' | '-' | '_' => Some(Obj::Space),
+			'$' => Some(Obj::Boxx),
+			'.' => Some(Obj::Hole),
+			'@' => Some(Obj::Human),
+			'+' => Some(Obj::HumanInHole),
+			'*' => Some(Obj::BoxxInHole),
+			_ => None,
+		};
+	}
+
+	pub fn with_human(&self) -> Obj {
+		match self {
+			Obj::Space => Obj::Human,
+			Obj::Hole => Obj::HumanInHole,
+			_ => panic!("Human cannot be placed there!"),
+		}
+	}
+
+	pub fn with_boxx(&self) -> Obj {
+		match self {
+			Obj::Space => Obj::Boxx,
+			Obj::Hole => Obj::BoxxInHole,
+			_ => panic!("Boxx cannot be placed there!"),
+		}
+	}
+
+	pub fn without_sprite(&self) -> Obj {
+		// removes any human or boxx, leaving what is underneath
+		match self {
+			Obj::Human | Obj::Boxx => Obj::Space,
+			Obj::HumanInHole | Obj::BoxxInHole => Obj::Hole,
+			_ => *self,
+		}
+	}
+}
+
+
+#[derive(Clone,PartialEq,Debug)]
+pub enum SaveStateError {
+	Level(LevelParseError),
+	MissingMoves,
+	Replay(ReplayError),
+}
+
+impl fmt::Display for SaveStateError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			SaveStateError::Level(e) => write!(f, "Saved game has a bad level: {}", e),
+			SaveStateError::MissingMoves => write!(f, "Saved game has no save_moves line!"),
+			SaveStateError::Replay(e) => write!(f, "Saved game moves don't replay: {}", e),
+		}
+	}
+}
+
+impl std::error::Error for SaveStateError {}
+
+impl From<LevelParseError> for SaveStateError {
+	fn from(e: LevelParseError) -> Self {
+		SaveStateError::Level(e)
+	}
+}
+
+impl From<ReplayError> for SaveStateError {
+	fn from(e: ReplayError) -> Self {
+		SaveStateError::Replay(e)
+	}
+}
+
+impl From<SaveStateError> for JsValue {
+	fn from(e: SaveStateError) -> Self {
+		js_sys::Error::new(&e.to_string()).into()
+	}
 }
 
 
@@ -81,13 +182,23 @@ impl Obj {
 pub struct Game {
 	pub level_number: u32,
 	pub num_moves: u32,
+	pub num_pushes: u32,
 	move_history: Vec::<Move>,
+	push_history: Vec::<bool>,		// whether each move in move_history pushed a boxx
+	redo_moves: Vec::<Move>,
 	pub human_pos: Vector,
+	base_level: Level,				// the level as it was before any moves, used for saving
 	level: Level,
 	sprites: Vec::<Sprite>,
 	move_queue: Vec::<Move>,
+	stuck_boxxes: Vec::<Vector>,	// boxxes that can never get to a hole, worked out after each push
+	clock: Box<dyn Clock>,			// time source for the sprite animations
+	move_duration: f64,				// how long each move is animated for, in ms (0 for no animation)
+	events: Vec::<GameEvent>,		// what has happened since the front end last asked (the latest MAX_EVENTS of it)
 }
 
+const MAX_EVENTS: usize = 1024;		// if the front end never drains the events, only keep this many
+
 
 impl Game {			// non-js
 	pub fn get_move_options(&self) -> Vec<Move> {
@@ -118,99 +229,241 @@ impl Game {			// non-js
 		self.move_queue.len() > 0
 	}
 	pub fn process_moves(&mut self)  {
-		if self.move_queue.len() == 0 { 
-			return; 
+		// apply queued moves until the human is busy moving (so with no animation, they all happen now)
+		while !self.sprites[0].is_moving(self.clock.now_ms()) {
+			let _move = match self.move_queue.pop() {
+				Some(m) => m,
+				None => return,
+			};
+
+			// a fresh move means there is nothing left to redo
+			if self.apply_move(&_move, true) {
+				self.redo_moves.clear();
+			}
+		}
+	}
+	pub fn new_headless(base_level: &Level, levelnum: u32) -> Game {
+		// a game with no animation, that doesn't look at the system clock
+		let mut game = Game::new_from_level(base_level, levelnum);
+		game.set_headless();
+		game
+	}
+	pub fn set_headless(&mut self) {
+		self.set_clock(Box::new(ManualClock::new(0.0)));
+		self.move_duration = 0.0;
+	}
+	pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+		// sprite times came from the old clock, so finish off any movement in progress
+		let now = clock.now_ms();
+		for sprite in self.sprites.iter_mut() {
+			let xy = sprite.final_xy;
+			sprite.set_xy(xy);
+			sprite.initial_time = now;
+		}
+		self.clock = clock;
+	}
+	pub fn set_move_duration(&mut self, ms: f64) {
+		self.move_duration = ms;
+	}
+	pub fn drain_events(&mut self) -> std::vec::Drain<'_, GameEvent> {
+		// everything that has happened since last time, oldest first
+		// if there's been more than MAX_EVENTS, the oldest have been thrown away
+		self.events.drain(..)
+	}
+	fn push_event(&mut self, ev: GameEvent) {
+		if self.events.len() >= MAX_EVENTS {
+			self.events.drain(..MAX_EVENTS/2);		// drop the oldest half, so we don't do this on every move
+		}
+		self.events.push(ev);
+	}
+	pub fn walk_to(&mut self, target: Vector) -> bool {
+		// replace any queued moves with the shortest walk to target (not pushing any boxxes)
+		self.move_queue.clear();
+		match self.level.walk_path(&self.human_pos, &target) {
+			Some(path) => {
+				path.iter().for_each(|m| self.append_move(m));
+				true
+			},
+			None => false,
+		}
+	}
+	pub fn push_box_to(&mut self, boxx: Vector, target: Vector) -> bool {
+		// replace any queued moves with the walks and pushes that get this boxx to target
+		self.move_queue.clear();
+		match self.level.push_path(&self.human_pos, &boxx, &target) {
+			Some(path) => {
+				path.iter().for_each(|m| self.append_move(m));
+				true
+			},
+			None => false,
+		}
+	}
+	pub fn undo(&mut self) -> bool {
+		// take back the last move, pulling back any boxx that was pushed
+		let _move = match self.move_history.pop() {
+			Some(m) => m,
+			None => return false,
+		};
+		let pushed = self.push_history.pop().unwrap();
+		self.move_queue.clear();
+
+		let boxx_pt = self.human_pos.add(&_move.to_vector());
+		let prev_human_pos = self.human_pos;
+		self.level.undo_move(&_move, pushed);
+		self.human_pos = self.level.human_pos;
+		self.num_moves -= 1;
+		if pushed {
+			self.num_pushes -= 1;
 		}
-		if self.sprites[0].is_moving() { 
-			return;
-		}			
-		
-		let	_move = self.move_queue.pop().unwrap();
 
-		// check it is a valid option
-		if !self.get_move_options().contains(&_move) {
-			return;
+		// sprites jump straight back, rather than animating
+		self.sprites[0].set_xy(self.human_pos);
+		if pushed {
+			self.move_boxx_sprite(&boxx_pt, &prev_human_pos, false);
+			self.stuck_boxxes = find_stuck_boxxes(&self.level);
 		}
 
-		// add to history
-		self.move_history.push(_move);
-		self.num_moves += 1;
+		// let the front end know what happened, the same as for a move (the boxx was pulled back to where the human was)
+		self.push_event(GameEvent::HumanMoved { from: prev_human_pos, to: self.human_pos });
+		if pushed {
+			self.push_event(GameEvent::BoxPushed { from: boxx_pt, to: prev_human_pos });
+			if self.level.get_obj_at_pt(&boxx_pt) == Obj::Hole {
+				self.push_event(GameEvent::BoxLeftHole { at: boxx_pt });
+			}
+			if self.level.get_obj_at_pt(&prev_human_pos) == Obj::BoxxInHole {
+				self.push_event(GameEvent::BoxEnteredHole { at: prev_human_pos });
+			}
+			if self.level.have_win_condition() {
+				self.push_event(GameEvent::LevelSolved { moves: self.num_moves, pushes: self.num_pushes });
+			}
+		}
 
-		// remove old human
-		let idx = self.human_pos.to_index(self.level.w);
-		let human_obj = self.level.get_obj_at_idx(idx);
-		let new_obj = match human_obj {
-			Obj::Human => { Obj::Space },
-			Obj::HumanInHole => { Obj::Hole },
-			_ => { panic!("Human not in tracked location!"); }
+		self.redo_moves.push(_move);
+		true
+	}
+	pub fn redo(&mut self) -> bool {
+		// re-apply the last move that was undone, this gives the same events as the move did the first time
+		let _move = match self.redo_moves.pop() {
+			Some(m) => m,
+			None => return false,
 		};
-		self.level.set_obj_at_idx(idx, new_obj);
-		
-		// new human point
-		let np = self.human_pos.add(&_move.to_vector());
-		let idx = np.to_index(self.level.w );	
-		
-		// check destination point
-		let obj = self.level.get_obj_at_idx(idx);
-		let mut moved_boxx = false;
-		let new_obj = match obj {
-			Obj::Space => { Obj::Human },
-			Obj::Hole  => { Obj::HumanInHole },
-			Obj::Boxx | Obj::BoxxInHole => {  
-				// Move boxx in to next square
-				moved_boxx = true;
-				let boxx_pt = &self.human_pos.add(&_move.to_vector().double());
-				let i = boxx_pt.to_index(self.level.w);
-				let o = self.level.get_obj_at_idx(i);
-				if o == Obj::Hole {
-					self.level.set_obj_at_idx(i, Obj::BoxxInHole);
-				} else {
-					self.level.set_obj_at_idx(i, Obj::Boxx);
-				}
-			
-				// We pushed the boxx
-				if obj == Obj::BoxxInHole {
-					Obj::HumanInHole
-				} else {
-					Obj::Human
-				}
+		self.move_queue.clear();
+		if !self.apply_move(&_move, false) {
+			// shouldn't happen, the redo list no longer matches the level
+			self.redo_moves.clear();
+			return false;
+		}
+		true
+	}
+	pub fn save_state(&self) -> String {
+		// the starting level, plus the moves we've made since
+		let mut s = self.base_level.to_file_string();
+		s += &format!("save_level_number: {}\n", self.level_number);
+		s += &format!("save_moves: {}\n", self.get_moves_string_lurd());
+		s
+	}
+	pub fn load_state(state: &str) -> Result<Game, SaveStateError> {
+		let mut base_level = Level::from_str(state)?;
+		let level_number = base_level.remove_keyval("save_level_number").unwrap_or_default().parse::<u32>().unwrap_or(0);
+		let moves = match base_level.remove_keyval("save_moves") {
+			Some(m) => m,
+			None => return Err(SaveStateError::MissingMoves),
+		};
+
+		// replay the moves to get back to where we were
+		let mut game = Game::new_from_level(&base_level, level_number);
+		for (index, _move) in string_to_moves(&moves)?.iter().enumerate() {
+			if !game.apply_move(_move, false) {
+				return Err(ReplayError::IllegalMove { index, mv: *_move }.into());
+			}
+		}
+		game.events.clear();		// the replay isn't news to anyone
+		Ok(game)
+	}
+	pub fn is_dead_square(&self, pt: &Vector) -> bool {
+		// a boxx pushed here can never get to a hole
+		self.base_level.in_noboxx_pts(pt)
+	}
+	pub fn get_dead_squares(&self) -> Vec::<Vector> {
+		self.base_level.get_noboxx_pts().clone()
+	}
+	pub fn get_stuck_boxes(&self) -> &Vec::<Vector> {
+		&self.stuck_boxxes
+	}
+	fn apply_move(&mut self, _move: &Move, animate: bool) -> bool {
+		let initial_pt = self.human_pos;
+		let pushed = match self.level.do_move(_move) {
+			Some(p) => p,
+			None => {						// not a valid option
+				self.push_event(GameEvent::MoveRejected { mv: *_move });
+				return false;
 			},
-			_ => { panic!("Human not allowed there!"); }
 		};
+		let np = self.level.human_pos;
+
+		// add to history
+		self.move_history.push(*_move);
+		self.push_history.push(pushed);
+		self.num_moves += 1;
+		if pushed {
+			self.num_pushes += 1;
+		}
 
 		// set up a visual transition for this move
 		// locate sprite human (always first one in the vec), replace sprite times + xy coordinates
-		
-		let trans = Trans {
-			initial_xy: self.human_pos.clone(),
-			final_xy: np.clone(),
-			duration: 100_f64,
-		};
-		self.sprites[0].apply_trans(trans);
-
-		// if we moved the boxx, we got to figure out which boxx it is, and update the sprite with a trans
-		if moved_boxx {
-			let initial_boxx_pt = &np;
-			let final_boxx_pt = &self.human_pos.add(&_move.to_vector().double());
-			let mut i = 0;
-			while i < self.sprites.len()-1 {
-				i += 1;
-				if self.sprites[i].obj == Obj::Boxx && self.sprites[i].final_xy == *initial_boxx_pt {
-					// move this one
-					let trans = Trans {
-						initial_xy: initial_boxx_pt.clone(),
-						final_xy: final_boxx_pt.clone(),
-						duration: 100_f64,
-					};
-					self.sprites[i].apply_trans(trans);
-					break;
-				}
-			}
+		if animate && self.move_duration > 0.0 {
+			let trans = Trans {
+				initial_xy: initial_pt,
+				final_xy: np,
+				duration: self.move_duration,
+			};
+			self.sprites[0].apply_trans(trans, self.clock.now_ms());
+		} else {
+			self.sprites[0].set_xy(np);
 		}
 
-		// place human
-		self.level.set_obj_at_idx(idx, new_obj);	
-		self.human_pos = np;		
+		// if we moved the boxx, we got to figure out which boxx it is, and update the sprite too
+		if pushed {
+			let final_boxx_pt = np.add(&_move.to_vector());
+			self.move_boxx_sprite(&np, &final_boxx_pt, animate);
+			self.stuck_boxxes = find_stuck_boxxes(&self.level);
+		}
+
+		self.human_pos = np;
+
+		// let the front end know what happened
+		self.push_event(GameEvent::HumanMoved { from: initial_pt, to: np });
+		if pushed {
+			let final_boxx_pt = np.add(&_move.to_vector());
+			self.push_event(GameEvent::BoxPushed { from: np, to: final_boxx_pt });
+			if self.level.get_obj_at_pt(&np) == Obj::HumanInHole {
+				self.push_event(GameEvent::BoxLeftHole { at: np });
+			}
+			if self.level.get_obj_at_pt(&final_boxx_pt) == Obj::BoxxInHole {
+				self.push_event(GameEvent::BoxEnteredHole { at: final_boxx_pt });
+			}
+			if self.level.have_win_condition() {
+				self.push_event(GameEvent::LevelSolved { moves: self.num_moves, pushes: self.num_pushes });
+			}
+		}
+		true
+	}
+	fn move_boxx_sprite(&mut self, from: &Vector, to: &Vector, animate: bool) {
+		// the human sprite is always first, so skip it
+		let now = self.clock.now_ms();
+		let sprite = self.sprites.iter_mut().skip(1).find(|s| s.obj == Obj::Boxx && s.final_xy == *from);
+		if let Some(sprite) = sprite {
+			if animate && self.move_duration > 0.0 {
+				let trans = Trans {
+					initial_xy: *from,
+					final_xy: *to,
+					duration: self.move_duration,
+				};
+				sprite.apply_trans(trans, now);
+			} else {
+				sprite.set_xy(*to);
+			}
+		}
 	}
 
 }
@@ -231,17 +484,26 @@ impl Game {
 
 	pub fn new_from_level(base_level: &Level, levelnum: u32) -> Game {
 		// restarts the game, using what's in base_level
+		let clock = SystemClock;
 		let mut sp = Vec::with_capacity(32);
-		sp.push(Sprite::new(0, Obj::Human, get_time_ms(), 0.0, base_level.human_pos, base_level.human_pos));
-		base_level.get_boxx_pts().iter().enumerate().for_each(|(n,b)| sp.push(Sprite::new(n as u32, Obj::Boxx, get_time_ms(), 0.0, *b, *b)));
+		sp.push(Sprite::new(0, Obj::Human, clock.now_ms(), 0.0, base_level.human_pos, base_level.human_pos));
+		base_level.get_boxx_pts().iter().enumerate().for_each(|(n,b)| sp.push(Sprite::new(n as u32, Obj::Boxx, clock.now_ms(), 0.0, *b, *b)));
 		Game {
 			level_number: levelnum,
 			num_moves: 0,
+			num_pushes: 0,
 			move_history: Vec::<Move>::new(),
+			push_history: Vec::<bool>::new(),
+			redo_moves: Vec::<Move>::new(),
 			human_pos: base_level.human_pos.clone(),
+			base_level: base_level.clone(),
 			level: base_level.clone(),
 			sprites: sp,
 			move_queue: Vec::<Move>::new(),
+			stuck_boxxes: find_stuck_boxxes(base_level),
+			clock: Box::new(clock),
+			move_duration: 100_f64,
+			events: Vec::<GameEvent>::new(),
 		}
 	}
 
@@ -249,6 +511,54 @@ impl Game {
 		self.process_moves();
 	}
 
+	pub fn walk_to_js(&mut self, x: i32, y: i32) -> bool {
+		self.walk_to(Vector(x, y))
+	}
+
+	pub fn push_box_to_js(&mut self, box_x: i32, box_y: i32, target_x: i32, target_y: i32) -> bool {
+		self.push_box_to(Vector(box_x, box_y), Vector(target_x, target_y))
+	}
+
+	pub fn undo_js(&mut self) -> bool {
+		self.undo()
+	}
+
+	pub fn redo_js(&mut self) -> bool {
+		self.redo()
+	}
+
+	pub fn save_state_js(&self) -> String {
+		self.save_state()
+	}
+
+	pub fn load_state_js(state: &str) -> Result<Game, JsValue> {
+		Ok(Game::load_state(state)?)
+	}
+
+	pub fn is_dead_square_js(&self, x: i32, y: i32) -> bool {
+		self.is_dead_square(&Vector(x, y))
+	}
+
+	pub fn is_deadlocked(&self) -> bool {
+		// there's no way to finish the level from here, without undoing
+		!self.stuck_boxxes.is_empty()
+	}
+
+	pub fn drain_events_js(&mut self) -> Array {
+		// as EventInfo
+		self.drain_events().map(|e| JsValue::from(e.to_info())).collect()
+	}
+
+	pub fn get_stuck_boxes_js(&self) -> Array {
+		self.stuck_boxxes.iter().map(|pt| JsValue::from(pt.as_array())).collect()
+	}
+
+	pub fn get_dead_squares_js(&self) -> Array {
+		// one flag per square, in the same order as get_level_data()
+		(0..self.level.h as i32).flat_map(|y| (0..self.level.w as i32).map(move |x| Vector(x, y)))
+			.map(|pt| JsValue::from(self.is_dead_square(&pt))).collect()
+	}
+
 	pub fn get_max_level_number(&self) -> u32 {
 		(BUILTIN_LEVELS.len() - 1) as u32
 	}
@@ -274,10 +584,18 @@ impl Game {
 		self.num_moves
 	}
 
+	pub fn get_num_pushes(&self) -> u32 {
+		self.num_pushes
+	}
+
 	pub fn get_moves_string(&self) -> String {
 		moves_to_string(&self.move_history)
 	}
 
+	pub fn get_moves_string_lurd(&self) -> String {
+		moves_to_lurd_string(&self.move_history, &self.push_history)
+	}
+
 	pub fn get_level_string(&self) -> String {
 		self.level.to_string()
 	}
@@ -324,7 +642,8 @@ impl Game {
 
 	pub fn get_sprites_js(&mut self) -> Array {
 		// return all the sprites, with their up-to-date-coordinates, as type SpriteInfo
-		self.sprites.clone().into_iter().map(|mut s| JsValue::from(s.get_sprite_info())).collect()
+		let now = self.clock.now_ms();
+		self.sprites.clone().into_iter().map(|mut s| JsValue::from(s.get_sprite_info(now))).collect()
 	}
 
 	pub fn get_sprites_debug(&mut self) -> Array {
@@ -340,3 +659,78 @@ pub fn moves_to_string(moves: &Vec::<Move>) -> String {
 	}
 	return s;
 }
+
+// Standard sokoban notation: walking moves are lower case, pushes are upper case
+pub fn moves_to_lurd_string(moves: &[Move], pushes: &[bool]) -> String {
+	let mut s: String = "".to_string();
+	for (m,p) in moves.iter().zip(pushes.iter()) {
+		if *p {
+			s += &m.to_string();
+		} else {
+			s += &m.to_string().to_lowercase();
+		}
+	}
+	s
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn play(game: &mut Game, path: &str) {
+		string_to_moves(path).unwrap().iter().for_each(|m| game.append_move(m));
+		game.process_moves();
+	}
+
+	#[test]
+	fn headless_undo_redo_counts() {
+		let level = Level::from_str("#######\n# &*O #\n#######\n").unwrap();
+		let mut game = Game::new_headless(&level, 0);
+		play(&mut game, "lrR");
+		assert_eq!((game.get_num_moves(), game.get_num_pushes()), (3, 1));
+		assert_eq!(game.get_moves_string_lurd(), "lrR");
+		assert!(game.have_win_condition());
+
+		assert!(game.undo());
+		assert_eq!((game.get_num_moves(), game.get_num_pushes()), (2, 0));
+		assert!(!game.have_win_condition());
+		assert!(game.undo() && game.undo());
+		assert!(!game.undo());
+		assert_eq!((game.get_num_moves(), game.get_num_pushes()), (0, 0));
+		assert_eq!(game.get_level_string(), level.to_string());
+
+		assert!(game.redo() && game.redo() && game.redo());
+		assert!(!game.redo());
+		assert_eq!((game.get_num_moves(), game.get_num_pushes()), (3, 1));
+		assert_eq!(game.get_moves_string_lurd(), "lrR");
+		assert!(game.have_win_condition());
+
+		// a fresh move after an undo clears the redo list
+		assert!(game.undo());
+		play(&mut game, "l");
+		assert!(!game.redo());
+		assert_eq!((game.get_num_moves(), game.get_num_pushes()), (3, 0));
+	}
+
+	#[test]
+	fn manual_clock_steps_moves() {
+		let level = Level::from_str("#######\n# &*O #\n#######\n").unwrap();
+		let mut game = Game::new_from_level(&level, 0);
+		let clock = ManualClock::new(1000.0);
+		game.set_clock(Box::new(clock.clone()));
+		game.set_move_duration(100.0);
+
+		// each move waits for the one before to finish animating
+		play(&mut game, "lrR");
+		assert_eq!(game.get_num_moves(), 1);
+		game.process_moves();
+		assert_eq!(game.get_num_moves(), 1);
+		clock.advance(100.0);
+		game.process_moves();
+		assert_eq!(game.get_num_moves(), 2);
+		clock.advance(100.0);
+		game.process_moves();
+		assert_eq!((game.get_num_moves(), game.get_num_pushes()), (3, 1));
+		assert!(!game.is_queued_moves());
+	}
+}
@@ -0,0 +1,266 @@
+// boxboppertool Copyright 2020-2021 David Atkinson
+//
+// astar.rs: solve a sokoban-style level with A*, expanding the most promising maps first
+//
+// Maps are ordered by moves so far + a lower bound on the moves left. The lower bound is the cheapest way of
+// matching up boxxes and holes, where each boxx costs the number of pushes it needs to reach its hole (ignoring
+// the other boxxes). Each push moves one boxx one square, so the bound drops by at most one per move, which
+// means the first solution we take off the queue is the shortest.
+//
+// When counting pushes, costs are (pushes, moves) pairs compared in that order. The bound works for both
+// halves, as every push is also a move.
+
+use boxbopperbase::{Obj};
+use boxbopperbase::level::{Level,CmpData,SpLevel};
+use boxbopperbase::time::{get_time_ms};
+use boxbopperbase::vector::{Vector,ALLMOVES};
+
+use crate::pathnodemap::{PathMap};
+use crate::solve::{Solution,Metric};
+
+use std::collections::{BinaryHeap,HashMap,VecDeque};
+use std::cmp::Ordering;
+
+const NO_ROUTE: u32 = 1 << 16;		// more pushes than any level could need
+
+pub struct PushDistances {
+	w: u16,
+	holes: Vec::<Vector>,
+	dists: Vec::<Vec::<u32>>,		// dists[hole][idx]: fewest pushes to get a boxx from idx to the hole, with no other boxxes about
+}
+
+impl PushDistances {
+	pub fn new(base_level: &Level) -> PushDistances {
+		// work backwards from each hole, pulling a boxx like do_noboxx_pts does
+		let size = base_level.w as usize * base_level.h as usize;
+		let mut holes = Vec::<Vector>::new();
+		for i in 0..size {
+			let o = base_level.get_obj_at_idx(i);
+			if o == Obj::Hole || o == Obj::HumanInHole || o == Obj::BoxxInHole {
+				holes.push(Vector((i % base_level.w as usize) as i32, (i / base_level.w as usize) as i32));
+			}
+		}
+		let mut dists = Vec::<Vec::<u32>>::with_capacity(holes.len());
+		for hole in holes.iter() {
+			let mut dist = vec![NO_ROUTE; size];
+			let mut queue = VecDeque::<Vector>::new();
+			dist[hole.to_index(base_level.w)] = 0;
+			queue.push_back(*hole);
+			while let Some(pt) = queue.pop_front() {
+				let d = dist[pt.to_index(base_level.w)];
+				for movedir in ALLMOVES.iter() {
+					let npt = pt.add_dir(movedir);
+					if base_level.get_obj_at_pt_checked(&npt) == Obj::Wall || base_level.get_obj_at_pt_checked(&pt.add_dir2(movedir)) == Obj::Wall {
+						continue;
+					}
+					if dist[npt.to_index(base_level.w)] == NO_ROUTE {
+						dist[npt.to_index(base_level.w)] = d + 1;
+						queue.push_back(npt);
+					}
+				}
+			}
+			dists.push(dist);
+		}
+		PushDistances { w: base_level.w, holes, dists }
+	}
+	pub fn lower_bound(&self, level: &SpLevel) -> u32 {
+		// fewest pushes needed to get every boxx into a hole, NO_ROUTE or more if we can't
+		let mut boxxes = Vec::<usize>::with_capacity(self.holes.len());
+		for i in 0..self.dists[0].len() {
+			let pt = Vector((i % self.w as usize) as i32, (i / self.w as usize) as i32);
+			if level.is_boxx_at_pt(&pt) {
+				boxxes.push(i);
+			}
+		}
+		let cost: Vec::<Vec::<u32>> = boxxes.iter().map(|b| self.dists.iter().map(|d| d[*b]).collect()).collect();
+		min_assignment(&cost)
+	}
+}
+
+pub fn min_assignment(cost: &[Vec::<u32>]) -> u32 {
+	// Hungarian algorithm, O(n^3): the cheapest way to give each row its own column
+	// rows and columns are numbered from 1, with 0 as a dummy column to start from
+	let n = cost.len();
+	let inf = i64::MAX / 4;
+	let mut u = vec![0_i64; n+1];
+	let mut v = vec![0_i64; n+1];
+	let mut p = vec![0_usize; n+1];		// p[col] = row assigned to col
+	let mut way = vec![0_usize; n+1];
+	for i in 1..=n {
+		p[0] = i;
+		let mut j0 = 0;
+		let mut minv = vec![inf; n+1];
+		let mut used = vec![false; n+1];
+		loop {
+			used[j0] = true;
+			let i0 = p[j0];
+			let mut delta = inf;
+			let mut j1 = 0;
+			for j in 1..=n {
+				if !used[j] {
+					let cur = cost[i0-1][j-1] as i64 - u[i0] - v[j];
+					if cur < minv[j] {
+						minv[j] = cur;
+						way[j] = j0;
+					}
+					if minv[j] < delta {
+						delta = minv[j];
+						j1 = j;
+					}
+				}
+			}
+			for j in 0..=n {
+				if used[j] {
+					u[p[j]] += delta;
+					v[j] -= delta;
+				} else {
+					minv[j] -= delta;
+				}
+			}
+			j0 = j1;
+			if p[j0] == 0 {
+				break;
+			}
+		}
+		// unwind the augmenting path
+		loop {
+			let j1 = way[j0];
+			p[j0] = p[j1];
+			j0 = j1;
+			if j0 == 0 {
+				break;
+			}
+		}
+	}
+	(1..=n).map(|j| cost[p[j]-1][j-1]).sum()
+}
+
+type Cost = (u16,u16);		// (moves, 0), or (pushes, moves)
+
+struct OpenMap {
+	f: Cost,		// cost so far + lower bound on cost left
+	g: Cost,		// cost so far
+	map: PathMap,
+}
+
+impl PartialEq for OpenMap {
+	fn eq(&self, other: &Self) -> bool {
+		self.f == other.f && self.g == other.g
+	}
+}
+
+impl Eq for OpenMap {}
+
+impl PartialOrd for OpenMap {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for OpenMap {
+	fn cmp(&self, other: &Self) -> Ordering {
+		// BinaryHeap pops the greatest: we want the lowest f, and then the highest cost so far (closest to done)
+		other.f.cmp(&self.f).then(self.g.cmp(&other.g))
+	}
+}
+
+fn cost(metric: Metric, map: &PathMap) -> Cost {
+	match metric {
+		Metric::Moves => (map.path.len(), 0),
+		Metric::Pushes | Metric::PushesThenMoves => (map.depth, map.path.len()),
+	}
+}
+
+fn add_bound(metric: Metric, g: Cost, h: u16) -> Cost {
+	match metric {
+		Metric::Moves => (g.0 + h, 0),
+		Metric::Pushes | Metric::PushesThenMoves => (g.0 + h, g.1 + h),
+	}
+}
+
+pub fn solve_level_astar(base_level_in: &Level, metric: Metric, max_moves_requested: u16, max_maps: usize, verbosity: u32) -> Option<Solution> {
+	let max_moves = max_moves_requested as u32;
+	let base_level1 = base_level_in.clear_human_cloned();
+	let base_map = PathMap::new_from_level(&base_level1);
+	let _path_root = base_map.path.clone(); 	// we have to keep reference so the root doesn't dissappear
+	let base_level = base_level1.clear_boxxes_cloned();
+	let pd = PushDistances::new(&base_level);
+
+	let msecs0 = get_time_ms();
+
+	let h = pd.lower_bound(&base_map.level);
+	if h > max_moves {
+		if verbosity > 0 { println!("-- No solution found --"); }
+		return None;
+	}
+
+	let mut best_g = HashMap::<CmpData,Cost>::new();		// lowest cost we've found to get to each map
+	let mut open = BinaryHeap::<OpenMap>::new();
+	best_g.insert(base_map.level.cmp_data.clone(), (0,0));
+	open.push(OpenMap { f: add_bound(metric, (0,0), h as u16), g: (0,0), map: base_map });
+
+	let mut bound: u16 = 0;
+	let mut expanded: usize = 0;
+	let mut children = Vec::<PathMap>::new();
+
+	while let Some(om) = open.pop() {
+		// skip maps we've since found a shorter way to
+		if best_g.get(&om.map.level.cmp_data).is_some_and(|g| *g < om.g) {
+			continue;
+		}
+
+		if om.f.0 > bound {
+			bound = om.f.0;
+			if verbosity > 0 { println!("-- Bound {:>3} -- (expanded {}, queued {}, seen {})", bound, expanded, open.len(), best_g.len()); }
+		}
+
+		if om.map.level.have_win_condition(&base_level) {
+			let path = om.map.path.to_string();
+			let moves = om.map.path.len();
+			if verbosity > 0 {
+				println!("-- Best solution --");
+				println!("Solution in {} moves, {} pushes: {}", moves, om.map.depth, path);
+			}
+			return Some(Solution {
+				secs: (get_time_ms() - msecs0) / 1000_f64,
+				moves,
+				pushes: om.map.depth,		// each key move is one push
+				depth: om.map.depth,
+				path,
+			});
+		}
+
+		// Perform next key moves
+		expanded += 1;
+		children.clear();
+		om.map.complete_solve_2(&base_level, &mut children);
+		for mut child in children.drain(..) {
+			child.depth = om.map.depth + 1;
+			if metric == Metric::Pushes {
+				child.canonicalise_human(&base_level);		// moves don't matter, so the human can be anywhere they can walk to
+			}
+			let g = cost(metric, &child);
+			if best_g.get(&child.level.cmp_data).is_some_and(|bg| *bg <= g) {
+				continue;
+			}
+			let h = pd.lower_bound(&child.level);
+			if child.path.len() as u32 + h > max_moves {
+				continue;
+			}
+			best_g.insert(child.level.cmp_data.clone(), g);
+			open.push(OpenMap { f: add_bound(metric, g, h as u16), g, map: child });
+		}
+
+		// Check if we've hit max_maps (our memory/resource limit)
+		if best_g.len() > max_maps * 4 || open.len() > max_maps {
+			println!("--- Hit maximum maps ({}) ---", max_maps);
+			break;
+		}
+	}
+
+	if verbosity > 0 {
+		println!("-- No solution found --");
+		println!("Max moves was {}", max_moves);
+	}
+	None
+}
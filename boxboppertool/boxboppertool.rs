@@ -4,17 +4,21 @@
 
 use std::fs::File;
 use std::io::prelude::*;
+use std::collections::HashMap;
 use rand::{Rng, SeedableRng};
 
 use boxbopperbase::{Obj};
-use boxbopperbase::level::{Level,verify_builtins};
-use boxbopperbase::vector::{Vector,ALLMOVES};
+use boxbopperbase::level::{Level,verify_builtins,MAX_WIDTH,MAX_HEIGHT};
+use boxbopperbase::collection::{LevelCollection};
+use boxbopperbase::vector::{Vector,ALLMOVES,Symmetry,ALLSYMMETRIES};
 
 pub mod defs;
 use defs::{*};
 
 pub mod solve;
-use solve::{solve_level,Solution};
+use solve::{solve_level_algo,Solution,Algo,Metric};
+
+pub mod astar;
 
 pub mod unsolve;
 use unsolve::{unsolve_level};
@@ -23,6 +27,10 @@ pub mod pathnodemap;
 
 pub mod shrunkpath;
 
+pub mod transtable;
+
+pub mod spill;
+
 extern crate rand;
 extern crate rand_chacha;
 
@@ -62,11 +70,12 @@ fn random_string(rng: &mut rand_chacha::ChaCha8Rng) -> String {
 }
 
 
-fn random_level_creator(width: u16, height: u16, wall_density: u32, box_density: u32, rng: &mut rand_chacha::ChaCha8Rng) -> (Level,String) {
+fn random_level_creator(width: u16, height: u16, wall_density: u32, box_density: u32, rng: &mut rand_chacha::ChaCha8Rng) -> (Level,Vec::<(String,String)>) {
 	let mut data = Vec::<Obj>::with_capacity(width as usize * height as usize);
-	let mut params = String::new();
+	let mut params = Vec::<(String,String)>::new();
 
-	params += &format!("width: {}\nheight: {}\n", width, height);
+	params.push(("width".to_string(), width.to_string()));
+	params.push(("height".to_string(), height.to_string()));
 
 	// fill with spaces
 	for _n in 0..(width * height) {
@@ -87,7 +96,7 @@ fn random_level_creator(width: u16, height: u16, wall_density: u32, box_density:
 			}
 		}
 	}
-	params += &format!("wall_density: {}\n", wall_density);
+	params.push(("wall_density".to_string(), wall_density.to_string()));
 
 	// create the level
 	let mut level = Level::from_parts(random_string(rng), width, height, human_pos, data);
@@ -119,8 +128,8 @@ fn random_level_creator(width: u16, height: u16, wall_density: u32, box_density:
 		println!("Warning: unable to place {} boxes, only placed {} boxes.", num_boxxes, i);
 		num_boxxes = i;
 	}
-	params += &format!("box_density: {}\n", box_density);
-	params += &format!("num_boxxes: {}\n", num_boxxes);
+	params.push(("box_density".to_string(), box_density.to_string()));
+	params.push(("num_boxxes".to_string(), num_boxxes.to_string()));
 
 	level.do_noboxx_pts();
 	level.do_boxx_pts();
@@ -135,6 +144,7 @@ struct SpeedTestData {
 	pub title: String,
 	pub depth: u16,
 	pub moves: u16,
+	pub pushes: Option<u16>,		// older files don't have this
 	pub path: String,
 	pub time: f64,
 }
@@ -185,6 +195,7 @@ impl SpeedTest {
 					moves: data[3].parse::<u16>().expect("Speed test read failed fourth column"),
 					path: data[4].to_string(),
 					time: data[5].parse::<f64>().expect("Speed test read failed sixth column"),
+					pushes: data.get(6).map(|d| d.parse::<u16>().expect("Speed test read failed seventh column")),
 				};
 				p.data.push(d);
 			}
@@ -197,7 +208,7 @@ impl SpeedTest {
 fn main() -> std::io::Result<()> {
 	let args: Vec::<String> = std::env::args().collect();
 	#[derive(PartialEq)]
-	enum Mode { Help, Solve, Make, SpeedTest }
+	enum Mode { Help, Solve, Make, SpeedTest, Dedupe, Check }
 	let mut mode = Mode::Help;
 	let mut seed: u32 = 0;
 	let mut max_moves: u16 = DEF_MAX_MOVES;
@@ -207,13 +218,22 @@ fn main() -> std::io::Result<()> {
 	let mut box_density: u32 = DEF_BOX_DENSITY;
 	let mut wall_density: u32 = DEF_WALL_DENSITY;
 	let mut max_maps: usize = DEF_MAX_MAPS;
+	let mut table_mem: usize = DEF_TABLE_MEM;
+	let mut table_mem_set = false;
+	let mut spill_dir: String = String::new();
 	let mut filename: String = String::from("");
 	let mut builtin: u32 = 0;
+	let mut index: usize = 0;
+	let mut symmetry: String = String::from("identity");
 	let mut verbosity: u32 = DEF_VERBOSITY;
 	let mut speed_test_read: String = String::new();
 	let mut speed_test_write: String = String::new();
 	let mut max_level: usize = DEF_MAX_LEVEL;
 	let mut num_threads: usize = 0;
+	let mut dir: String = String::from("levels");
+	let mut remove: bool = false;
+	let mut algo: String = String::from("bfs");
+	let mut metric: String = String::from("moves");
 	
 	// process params
 	for (count,arg) in args.into_iter().enumerate() {
@@ -222,8 +242,10 @@ fn main() -> std::io::Result<()> {
 				"solve" => { mode = Mode::Solve; },
 				"make"  => { mode = Mode::Make; },
 				"speed_test" => { mode = Mode::SpeedTest; verbosity = 0; }
+				"dedupe" => { mode = Mode::Dedupe; },
+				"check" => { mode = Mode::Check; },
 				_ => {
-					println!("First argument should be make or solve or speed_test");
+					println!("First argument should be make or solve or speed_test or dedupe or check");
 				}
 			};
 		} else if count >= 2 {
@@ -248,10 +270,18 @@ fn main() -> std::io::Result<()> {
 				"speed_test_read"  => { speed_test_read = String::from(right); },
 				"speed_test_write"  => { speed_test_write = String::from(right); },
 				"builtin"   => { builtin = right.parse::<u32>().unwrap(); }
+				"index"     => { index = right.parse::<usize>().unwrap(); }
+				"symmetry"  => { symmetry = String::from(right); },
 				"max_level"   => { max_level = right.parse::<usize>().unwrap(); }
 				"verbosity" => { verbosity = right.parse::<u32>().unwrap(); },
 				"threads" => { num_threads = right.parse::<usize>().unwrap(); },
 				"max_maps" => { max_maps = right.parse::<usize>().unwrap(); },
+				"table_mem" => { table_mem = right.parse::<usize>().unwrap(); table_mem_set = true; },
+				"spill_dir" => { spill_dir = String::from(right); },
+				"dir" => { dir = String::from(right); },
+				"remove" => { remove = right.parse::<bool>().unwrap(); },
+				"algo" => { algo = String::from(right); },
+				"metric" => { metric = String::from(right); },
 				_ => {
 					println!("Unrecognised variable {}", left);
 					mode = Mode::Help;
@@ -260,8 +290,8 @@ fn main() -> std::io::Result<()> {
 		}
 	}
 
-	if width > 127 || height > 127 || width * height > 256 {
-		println!("ERROR: Maximum width is 127. Maximum height is 127. Maximum width * height is 256.");
+	if width > MAX_WIDTH as usize || height > MAX_HEIGHT as usize {
+		println!("ERROR: Maximum width is {}. Maximum height is {}.", MAX_WIDTH, MAX_HEIGHT);
 		return Ok(());
 	} 
 
@@ -269,11 +299,35 @@ fn main() -> std::io::Result<()> {
 		num_threads = num_cpus::get();
 	}
 
+	let algo = match algo.parse::<Algo>() {
+		Ok(a) => a,
+		Err(e) => {
+			println!("Error: {}", e);
+			return Ok(());
+		},
+	};
+	let metric = match metric.parse::<Metric>() {
+		Ok(m) => m,
+		Err(e) => {
+			println!("Error: {}", e);
+			return Ok(());
+		},
+	};
+	if algo == Algo::AStar && (mode == Mode::Solve || mode == Mode::SpeedTest || mode == Mode::Make) {
+		// the A* search keeps all its maps in its own queue (make still uses the table when unsolving)
+		if !spill_dir.is_empty() {
+			println!("Warning: spill_dir is ignored with algo=astar");
+		}
+		if table_mem_set && mode != Mode::Make {
+			println!("Warning: table_mem is ignored with algo=astar");
+		}
+	}
+
 	let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0x0d47d47000000000_u64 + seed as u64);
 
 	if mode == Mode::Help {
 		println!("boxboppertool by David Atkinson 2020-2021\nthis program makes and solves boxbopper (sokoban-like) levels\n");
-		println!("boxboppertool make [vars...]\nboxboppertool solve [vars...]\nboxboppertool speed_test [vars...]\n");
+		println!("boxboppertool make [vars...]\nboxboppertool solve [vars...]\nboxboppertool speed_test [vars...]\nboxboppertool dedupe [vars...]\nboxboppertool check [vars...]\n");
 		println!("vars for make:");
 		println!("  seed=n           rng seed (u32)");
 		println!("  width=n          level width 5-15                              default: {}", DEF_WIDTH);
@@ -285,16 +339,32 @@ fn main() -> std::io::Result<()> {
 		println!("  max_moves=n      maximum number of moves to try 1+             default: {}", DEF_MAX_MOVES);
 		println!("  builtin=n        builtin level to solve");
 		println!("  filename=f       custom level filename to solve");
+		println!("  index=n          level to solve within filename 0+             default: 0");
+		println!("  symmetry=s       rotate / mirror the level first               default: identity");
+		println!("                   identity rotate_cw rotate_180 rotate_ccw flip_h flip_v transpose anti_transpose all");
 		println!("vars for speed_test:");
 		println!("  max_level=n         maximum level to test up to                default: {}", DEF_MAX_LEVEL);
 		println!("  speed_test_read=f   filename to compare results with");
 		println!("  speed_test_write=f  filename to write results to");
+		println!("vars for dedupe:");
+		println!("  dir=d            directory of level files to check             default: levels");
+		println!("  remove=b         delete the duplicates (true / false)          default: false");
+		println!("vars for check:");
+		println!("  filename=f       level file to check (all the levels in it)");
+		println!("  dir=d            directory to check, if no filename            default: levels");
 		println!("vars for all:");
 		println!("  verbosity=n      how much information to provide 0-2           default: {}", DEF_VERBOSITY);
 		println!("  threads=n        how many cpu threads to use 0=auto            default: 0");
+		println!("  algo=a           how to search for solutions: bfs or astar     default: bfs");
+		println!("  metric=m         what to minimise (not make)                   default: moves");
+		println!("                   moves pushes pushes_then_moves");
 		println!("  max_maps=n       max maps to have in memory                    default: {}", DEF_MAX_MAPS);
+		println!("  table_mem=n      megabytes for the table of seen maps          default: {}", DEF_TABLE_MEM);
+		println!("  spill_dir=d      where to put maps over max_maps (bfs only)    default: none, purge half the maps");
 		println!("");
-		println!("lower max_maps to reduce memory usage (but it may not solve)");
+		println!("lower max_maps or table_mem to reduce memory usage (but it may not solve, or be slower)");
+		println!("without spill_dir, going over max_maps throws half the maps out, which may lose the solution");
+		println!("set spill_dir to keep solving without throwing maps out, when there are more than max_maps");
 		println!("lower max_moves to improve performance (but it will not solve if more moves are required)");
 		println!("");
 	} else if mode == Mode::Make {
@@ -306,14 +376,14 @@ fn main() -> std::io::Result<()> {
 			println!("==== Unsolving level ===="); 
 			println!("{}", &random_level.to_string());
 		}
-		let unsolved_levels = unsolve_level(&random_level, max_depth, max_maps, &mut rng, verbosity, num_threads);
+		let unsolved_levels = unsolve_level(&random_level, max_depth, max_maps, table_mem, &mut rng, verbosity, num_threads);
 
 		let mut best_idx = None;
 		let mut solutions = Vec::<Option<Solution>>::new();
 		for x in 0..unsolved_levels.len() {
 			println!("==== Solving variation {} of {} ====", x, unsolved_levels.len()-1);
 			println!("{}", &unsolved_levels[x].to_string());
-			let solution = solve_level(&unsolved_levels[x], unsolved_levels[x].get_keyval("moves").parse::<u16>().expect("number->string->number failure!")+2, max_maps, verbosity, num_threads); // probably don't need the +2
+			let solution = solve_level_algo(algo, Metric::Moves, &unsolved_levels[x], unsolved_levels[x].get_keyval("moves").parse::<u16>().expect("number->string->number failure!")+2, max_maps, table_mem, &spill_dir, verbosity, num_threads); // probably don't need the +2
 			solutions.push(solution.clone());
 			match solution {
 				Some(solution) => {
@@ -359,19 +429,24 @@ fn main() -> std::io::Result<()> {
 
 		// pick best level
 		let solution = solutions[best_idx.unwrap()].as_ref().unwrap();
-		let mut unsolved_level = unsolved_levels[best_idx.unwrap()].clone();
-		unsolved_level.set_keyval("title",&format!("{}-{}",unsolved_level.get_title_str(),solution.moves));
+		// tidy up floor the human can't reach and excess walls before saving
+		let mut unsolved_level = unsolved_levels[best_idx.unwrap()].normalize();
+		let title = format!("{}-{}",unsolved_level.get_title_str(),solution.moves);
+
+		// replace what unsolve came up with, with what the solver found
+		unsolved_level.clear_keyvals();
+		unsolved_level.set_keyval("title", &title);
+		unsolved_level.set_keyval("depth", &solution.depth.to_string());
+		unsolved_level.set_keyval("moves", &solution.moves.to_string());
+		unsolved_level.set_keyval("path", &solution.path);
+		unsolved_level.set_keyval("time", &format!("{:.2}", solution.secs));
+		unsolved_level.set_keyval("seed", &seed.to_string());
+		for (k,v) in level_params.iter() {
+			unsolved_level.set_keyval(k, v);
+		}
 
 		println!("-- Chosen level {} --", best_idx.unwrap());
-		let mut output_str = String::new();
-		output_str += &format!("{}\n",unsolved_level.to_string());
-		output_str += &format!("title: {}\n", unsolved_level.get_title_str());
-		output_str += &format!("depth: {}\n", solution.depth);
-		output_str += &format!("moves: {}\n", solution.moves);
-		output_str += &format!("path: {}\n", solution.path);
-		output_str += &format!("time: {:.2}\n", solution.secs);
-		output_str += &format!("seed: {}\n", seed);
-		output_str += &format!("{}\n", level_params);
+		let output_str = unsolved_level.to_file_string();
 		println!("{}",output_str);
 
 		// save level to disk if it meets threshold
@@ -384,55 +459,175 @@ fn main() -> std::io::Result<()> {
 				return Err(std::io::Error::last_os_error());
 			}
 		}
+	} else if mode == Mode::Dedupe {
+		// find level files that are the same puzzle (maybe rotated, mirrored or padded out), keeping the first by name
+		let mut filenames = Vec::<String>::new();
+		for entry in std::fs::read_dir(&dir)? {
+			let path = entry?.path();
+			if path.is_file() && path.extension().is_some_and(|e| e == "txt") {
+				filenames.push(path.to_string_lossy().to_string());
+			}
+		}
+		filenames.sort();
+
+		let mut seen = HashMap::<u64,String>::new();
+		let mut num_dups = 0;
+		for f in filenames.iter() {
+			let level = match Level::from_file(f) {
+				Ok(l) => l,
+				Err(e) => {
+					println!("Skipping {}: {}", f, e);
+					continue;
+				},
+			};
+			let hash = level.canonical_hash();
+			if let Some(orig) = seen.get(&hash) {
+				num_dups += 1;
+				if remove {
+					std::fs::remove_file(f)?;
+					println!("{} is a duplicate of {}, removed", f, orig);
+				} else {
+					println!("{} is a duplicate of {}", f, orig);
+				}
+			} else {
+				if verbosity > 1 { println!("{} hash {:016x}", f, hash); }
+				seen.insert(hash, f.clone());
+			}
+		}
+		println!("{} files checked, {} duplicates{}.", filenames.len(), num_dups, if remove && num_dups > 0 { " removed" } else { "" });
+	} else if mode == Mode::Check {
+		// report problems with the levels in a file, or in every file in a directory
+		let mut filenames = Vec::<String>::new();
+		if !filename.is_empty() {
+			filenames.push(filename.clone());
+		} else {
+			for entry in std::fs::read_dir(&dir)? {
+				let path = entry?.path();
+				if path.is_file() && path.extension().is_some_and(|e| e == "txt") {
+					filenames.push(path.to_string_lossy().to_string());
+				}
+			}
+			filenames.sort();
+		}
+
+		let mut num_levels = 0;
+		let mut num_bad = 0;
+		for f in filenames.iter() {
+			let collection = match LevelCollection::from_file(f) {
+				Ok(c) => c,
+				Err(e) => {
+					println!("{}: {}", f, e);
+					num_bad += 1;
+					continue;
+				},
+			};
+			for i in 0..collection.len() {
+				num_levels += 1;
+				let level = match collection.get(i).unwrap() {
+					Ok(l) => l,
+					Err(e) => {
+						println!("{} index {}: {}", f, i, e);
+						num_bad += 1;
+						continue;
+					},
+				};
+				let report = level.analyze();
+				let verdict = if !report.is_solvable() {
+					num_bad += 1;
+					"unsolvable"
+				} else if !report.is_clean() {
+					"warnings"
+				} else {
+					"ok"
+				};
+				println!("{} index {} \"{}\": {}", f, i, level.get_title_str(), verdict);
+				if verdict != "ok" || verbosity > 1 {
+					print!("{}", report);
+				}
+			}
+		}
+		println!("{} levels checked, {} with problems.", num_levels, num_bad);
 	} else if mode == Mode::Solve {
 		// load level
-		// TODO: Fix from_file and from_builtin so they have same return type so the code is neater
-		let level: Level;
-		if filename.len() > 0 {
-			match Level::from_file(&filename) {
-				Ok(l) => level = l,
-				Err(s) => {
-					println!("Error: Unable to open level file: {}", s);
-					return Ok(());
+		let level = if filename.len() > 0 {
+			match LevelCollection::from_file(&filename) {
+				Ok(c) => match c.get(index) {
+					Some(l) => l.clone(),
+					None => {
+						println!("Error: Level index {} not found, file contains {} level(s)", index, c.len());
+						return Ok(());
+					},
 				},
+				Err(e) => Err(e),
 			}
 		} else {
-			match Level::from_builtin(builtin as usize) {
-				Ok(l) => level = l,
-				Err(s) => {
-					println!("Error: Unable to open builtin level {}: {}", builtin, s);
+			Level::from_builtin(builtin as usize)
+		};
+		let level = match level {
+			Ok(l) => l,
+			Err(e) => {
+				println!("Error: Unable to open level: {}", e);
+				return Ok(());
+			},
+		};
+
+		// which orientation(s) of the level to solve
+		let syms: Vec::<Symmetry> = if symmetry == "all" {
+			ALLSYMMETRIES.to_vec()
+		} else {
+			match symmetry.parse::<Symmetry>() {
+				Ok(sym) => vec![sym],
+				Err(e) => {
+					println!("Error: {}", e);
 					return Ok(());
 				},
 			}
-		}
+		};
 
-		if width > 127 || height > 127 || width * height > 256 {
-			println!("ERROR: Maximum width is 127. Maximum height is 127. Maximum width * height is 256.");
-			return Ok(());
-		} 
+		let mut results = Vec::<(Symmetry, Option<Solution>)>::new();
+		for sym in syms {
+			let level = level.transform(sym);
+			if filename.len() > 0 {
+				println!("Solving level \"{}\" (filename {}, index {}, symmetry {})...",level.get_title_str(), filename, index, sym);
+			} else {
+				println!("Solving level \"{}\" (builtin level {}, symmetry {})...",level.get_title_str(), builtin, sym);
+			}
+			
+			if verbosity > 0 { println!("{}",level.to_string()); }
 
-		if filename.len() > 0 {
-			println!("Solving level \"{}\" (filename {})...",level.get_title_str(), filename);
-		} else {
-			println!("Solving level \"{}\" (builtin level {})...",level.get_title_str(), builtin);
+			let solution = solve_level_algo(algo, metric, &level, max_moves, max_maps, table_mem, &spill_dir, verbosity, num_threads);
+			match &solution {
+				Some(sol) => {
+					let mut output_str = "".to_string();
+					output_str += &format!("title: {}\n", level.get_title_str());
+					output_str += &format!("depth: {}\n", sol.depth);
+					output_str += &format!("moves: {}\n", sol.moves);
+					output_str += &format!("pushes: {}\n", sol.pushes);
+					output_str += &format!("path: {}\n", sol.path);
+					output_str += &format!("time: {:.2}\n", (sol.secs));
+					println!("{}", output_str);
+				},
+				None => {
+				},
+			};
+			results.push((sym, solution));
+		}
+
+		// every orientation of a level should score the same
+		if results.len() > 1 {
+			for (sym, sol) in results.iter() {
+				match sol {
+					Some(s) => println!("{:15} {} moves, {} pushes", sym, s.moves, s.pushes),
+					None => println!("{:15} unsolved", sym),
+				}
+			}
+			let score = |r: &(Symmetry, Option<Solution>)| r.1.as_ref().map(|s| s.score(metric));
+			if results.iter().all(|r| score(r) == score(&results[0])) {
+				println!("All symmetries match.");
+			} else {
+				println!("Warning: symmetries do not match!");
+			}
 		}
-		
-		if verbosity > 0 { println!("{}",level.to_string()); }
-
-		let solution = solve_level(&level, max_moves, max_maps, verbosity, num_threads);
-		match solution {
-			Some(sol) => {
-				let mut output_str = "".to_string();
-				output_str += &format!("title: {}\n", level.get_title_str());
-				output_str += &format!("depth: {}\n", sol.depth);
-				output_str += &format!("moves: {}\n", sol.moves);
-				output_str += &format!("path: {}\n", sol.path);
-				output_str += &format!("time: {:.2}\n", (sol.secs));
-				println!("{}", output_str);
-			},
-			None => {
-			},
-		};
 	} else { // mode = speed_test
 		// Solve levels 0 to X and check they solved correctly
 		let mut success = true;
@@ -446,12 +641,12 @@ fn main() -> std::io::Result<()> {
 		if speed_test_read.len() > 0 {
 			p = SpeedTest::from_file(&speed_test_read);
 		}
-		let mut save_speed_test_string = String::from("# boxboppertool speed_test\n# num(u16), title(str), depth(u16), moves(u16), path(str), time(f64:s)\n");
+		let mut save_speed_test_string = format!("# boxboppertool speed_test algo={} metric={}\n# num(u16), title(str), depth(u16), moves(u16), path(str), time(f64:s), pushes(u16)\n", algo, metric);
 		for level_num in 0..=max_level {
 			let level = Level::from_builtin(level_num).expect(&format!("Unable to open builtin level {}!", level_num));
 			println!("Solving level {} \"{}\"...",level_num,level.get_keyval_or("title","untitled"));
 			
-			let solution = solve_level(&level, max_moves, max_maps, verbosity, num_threads);
+			let solution = solve_level_algo(algo, metric, &level, max_moves, max_maps, table_mem, &spill_dir, verbosity, num_threads);
 
 			match &solution {
 				Some(sol) => {
@@ -472,13 +667,17 @@ fn main() -> std::io::Result<()> {
 								println!("  Moves mismatch (prev: {}, this: {})", p.get_speed_test(level_num).unwrap().moves, sol.moves);
 								warnings = true;
 							}
+							if p.get_speed_test(level_num).unwrap().pushes.is_some_and(|pushes| pushes != sol.pushes) {
+								println!("  Pushes mismatch (prev: {}, this: {})", p.get_speed_test(level_num).unwrap().pushes.unwrap(), sol.pushes);
+								warnings = true;
+							}
 							if p.get_speed_test(level_num).unwrap().path != sol.path {
 								println!("  Path differs\n  prev: {}\n  this: {}", p.get_speed_test(level_num).unwrap().path, sol.path);
 								warnings = true;
 							}
 						}
 					}
-					save_speed_test_string += &format!("{}, {}, {}, {}, {}, {}\n", level_num, level.get_keyval_or("title","untitled"), sol.depth, sol.moves, sol.path, sol.secs);
+					save_speed_test_string += &format!("{}, {}, {}, {}, {}, {}, {}\n", level_num, level.get_keyval_or("title","untitled"), sol.depth, sol.moves, sol.path, sol.secs, sol.pushes);
 				},
 				None => {
 					println!("  Failed to find solution");
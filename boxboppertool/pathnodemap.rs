@@ -6,7 +6,6 @@
 use boxbopperbase::{Obj};
 use boxbopperbase::level::{Level,SpLevel,CmpData};
 use boxbopperbase::vector::{Vector,Move,ALLMOVES};
-use boxbopperbase::stackstack::{StackStack16x64,StackStack8x64};
 use crate::shrunkpath::{TreeNodeRef}; // ShrunkPath128, PathTrait,
 
 #[derive(Clone,Copy)]
@@ -31,6 +30,7 @@ pub struct PathNodeMap {
 #[derive(Clone)]
 pub struct PathMap {
 	pub level: SpLevel,
+	pub human_pos: Vector,		// where the human really is, level.cmp_data can have a canonical position instead
 	pub path: TreeNodeRef,
 	pub depth: u16,
 	pub flag: bool,
@@ -44,6 +44,7 @@ impl PathMap {
 				h: 0,
 				cmp_data: CmpData::new(),
 			},
+			human_pos: Vector(0,0),
 			path: TreeNodeRef::new_root(),
 			depth: 0,
 			flag: false,
@@ -52,6 +53,7 @@ impl PathMap {
 	pub fn new_from_level(level: &Level) -> PathMap {
 		PathMap {
 			level: SpLevel::from_level(level),
+			human_pos: level.human_pos,
 			path: TreeNodeRef::new_root(),
 			depth: 0,
 			flag: false,
@@ -59,7 +61,7 @@ impl PathMap {
 	}
 	pub fn to_pnm(&self) -> PathNodeMap {		// this one clones across our data
 		let initial_pn = PathNode {
-			pt: Vector(self.level.cmp_data.human_x as i32, self.level.cmp_data.human_y as i32),
+			pt: self.human_pos,
 			move_taken: None,
 			prev_node_idx: 0,
 		};
@@ -72,117 +74,99 @@ impl PathMap {
 	}
 	pub fn complete_solve_2(&self, base_level: &Level, maps_out: &mut Vec::<PathMap>) {		
 		let initial_pn = PathNode {
-			pt: Vector(self.level.cmp_data.human_x as i32, self.level.cmp_data.human_y as i32),
+			pt: self.human_pos,
 			move_taken: None,
 			prev_node_idx: 0,
 		};
 		let mut nodes = Vec::<PathNode>::with_capacity(256/(std::mem::size_of::<PathNode>()));
 		nodes.push(initial_pn);
 
-		let mut tail_nodes = StackStack16x64::new(); 
-		let mut new_tail_nodes = StackStack16x64::new(); 	// somewhere to store new tail nodes
-		tail_nodes.push(0);
-		while tail_nodes.len() != 0 {					// check if map is complete
-			for idx in 0..tail_nodes.len() {							// for each tail node
-				let tnidx = tail_nodes.stack[idx]; 
-				let tnode = nodes[tnidx as usize];
-				let pt = tnode.pt;									
-				'loop_moves: for movedir in ALLMOVES.iter() {			// for each possible move
-					let npt = pt.add_dir(&movedir);						// what is in this direction? let's find out
-					if !base_level.vector_in_bounds(&npt) { continue; }
-					if self.level.is_boxx_at_pt(&npt) {
-						// What's past the boxx? We can push into Space and Hole.
-						let bnpt = pt.add_dir2(&movedir);
-						let nobj = self.level.get_obj_at_pt_nohuman_checked(&bnpt, base_level);
-						if nobj == Obj::Space || nobj == Obj::Hole {
-							// yep, its a keymove, save key move.. but before we do, make sure it isn't a double boxx situation or in our noboxx list
-							if !base_level.in_noboxx_pts(&bnpt)  && !self.double_boxx_situation(pt,*movedir,base_level) {
-								let km = KeyMove {
-									pni: tnidx,
-									move_dir: *movedir,
-								};
-								//pnm.key_moves.push(km);
-								maps_out.push(self.apply_key_push_2(&nodes,&km));
-							}
-						} 
-					} else if base_level.get_obj_at_pt(&npt) != Obj::Wall {											
-						// first check this point isn't already in our list!!!						
-						for n in &nodes {
-							if n.pt == npt { continue 'loop_moves; }		// This is a hot spot 9.88%
+		let mut tnidx: u16 = 0;
+		while (tnidx as usize) < nodes.len() {				// nodes are a queue: each one is a tail node, until we've looked around it
+			let tnode = nodes[tnidx as usize];
+			let pt = tnode.pt;									
+			'loop_moves: for movedir in ALLMOVES.iter() {			// for each possible move
+				let npt = pt.add_dir(&movedir);						// what is in this direction? let's find out
+				if !base_level.vector_in_bounds(&npt) { continue; }
+				if self.level.is_boxx_at_pt(&npt) {
+					// What's past the boxx? We can push into Space and Hole.
+					let bnpt = pt.add_dir2(&movedir);
+					let nobj = self.level.get_obj_at_pt_nohuman_checked(&bnpt, base_level);
+					if nobj == Obj::Space || nobj == Obj::Hole {
+						// yep, its a keymove, save key move.. but before we do, make sure it isn't a double boxx situation or in our noboxx list
+						if !base_level.in_noboxx_pts(&bnpt)  && !self.double_boxx_situation(pt,*movedir,base_level) {
+							let km = KeyMove {
+								pni: tnidx,
+								move_dir: *movedir,
+							};
+							//pnm.key_moves.push(km);
+							maps_out.push(self.apply_key_push_2(&nodes,&km));
 						}
-
-						// yep, we can move here, make a new tail node
-						let pn = PathNode {
-							pt: npt.clone(),
-							move_taken: Some(*movedir),
-							prev_node_idx: tnidx as u16,
-						};
-						new_tail_nodes.push(nodes.len() as u16);
-						nodes.push(pn);
+					} 
+				} else if base_level.get_obj_at_pt(&npt) != Obj::Wall {											
+					// first check this point isn't already in our list!!!						
+					for n in &nodes {
+						if n.pt == npt { continue 'loop_moves; }		// This is a hot spot 9.88%
 					}
-				}	
-			}
-	
-			// move new_tail_nodes to tail_nodes
-			tail_nodes.clone_from(&new_tail_nodes);
-			new_tail_nodes.clear();
+
+					// yep, we can move here, make a new tail node
+					let pn = PathNode {
+						pt: npt.clone(),
+						move_taken: Some(*movedir),
+						prev_node_idx: tnidx,
+					};
+					nodes.push(pn);
+				}
+			}	
+			tnidx += 1;
 		}
 		// pnm -> new_by_applying_key_push(pnm, pm, km)
 	}
 	pub fn complete_unsolve_2(&self, base_level: &Level, maps_out: &mut Vec::<PathMap>, depth: u16) {
 		let initial_pn = PathNode {
-			pt: Vector(self.level.cmp_data.human_x as i32, self.level.cmp_data.human_y as i32),
+			pt: self.human_pos,
 			move_taken: None,
 			prev_node_idx: 0,
 		};
 		let mut nodes = Vec::<PathNode>::with_capacity(256/(std::mem::size_of::<PathNode>()));
 		nodes.push(initial_pn);
 		
-		let mut tail_nodes = StackStack16x64::new(); 
-		let mut new_tail_nodes = StackStack16x64::new();
-		tail_nodes.push(0);		
-		while tail_nodes.len() != 0 {					// check if map is complete
-			for idx in 0..tail_nodes.len() {
-				let tnidx = tail_nodes.stack[idx];
-				let tnode = nodes[tnidx as usize];
-				let pt = tnode.pt;									
-				'loop_moves: for movedir in ALLMOVES.iter() {							// for each possible move
-					let npt = pt.add_dir(&movedir);							// what is in this direction? let's find out	
-					if !base_level.vector_in_bounds(&npt) { continue; }
-					if self.level.is_boxx_at_pt(&npt) {
-						// What's in our reverse direction? We can pull into Space and Hole.
-						let bnpt = pt.add_dir(&movedir.reverse());
-						let nobj = self.level.get_obj_at_pt_nohuman_checked(&bnpt, base_level);
-						if nobj == Obj::Space || nobj == Obj::Hole {
-							// yep, its a keypull, save key move.. 
-							let km = KeyMove {
-								pni: tnidx,
-								move_dir: movedir.reverse(),
-							};
-							//pnm.key_moves.push(km);
-							maps_out.push(self.apply_key_pull_2(&nodes,&km, depth));
-						}
-					} else if base_level.get_obj_at_pt(&npt) != Obj::Wall {
-						// first check this point isn't already in our list!!!						
-						for n in nodes.iter() {
-							if n.pt == npt { continue 'loop_moves; }
-						}
-						
-						// yep, we can move here, make a new tail node
-						let pn = PathNode {
-							pt: npt.clone(),
-							move_taken: Some(*movedir),
-							prev_node_idx: tnidx as u16,
+		let mut tnidx: u16 = 0;
+		while (tnidx as usize) < nodes.len() {				// nodes are a queue: each one is a tail node, until we've looked around it
+			let tnode = nodes[tnidx as usize];
+			let pt = tnode.pt;									
+			'loop_moves: for movedir in ALLMOVES.iter() {							// for each possible move
+				let npt = pt.add_dir(&movedir);							// what is in this direction? let's find out	
+				if !base_level.vector_in_bounds(&npt) { continue; }
+				if self.level.is_boxx_at_pt(&npt) {
+					// What's in our reverse direction? We can pull into Space and Hole.
+					let bnpt = pt.add_dir(&movedir.reverse());
+					let nobj = self.level.get_obj_at_pt_nohuman_checked(&bnpt, base_level);
+					if nobj == Obj::Space || nobj == Obj::Hole {
+						// yep, its a keypull, save key move.. 
+						let km = KeyMove {
+							pni: tnidx,
+							move_dir: movedir.reverse(),
 						};
-						new_tail_nodes.push(nodes.len() as u16);
-						nodes.push(pn);
+						//pnm.key_moves.push(km);
+						maps_out.push(self.apply_key_pull_2(&nodes,&km, depth));
 					}
-				}	
-			}
-
-			// move new_tail_nodes to tail_nodes
-			tail_nodes.clone_from(&new_tail_nodes);
-			new_tail_nodes.clear();
+				} else if base_level.get_obj_at_pt(&npt) != Obj::Wall {
+					// first check this point isn't already in our list!!!						
+					for n in nodes.iter() {
+						if n.pt == npt { continue 'loop_moves; }
+					}
+					
+					// yep, we can move here, make a new tail node
+					let pn = PathNode {
+						pt: npt.clone(),
+						move_taken: Some(*movedir),
+						prev_node_idx: tnidx,
+					};
+					nodes.push(pn);
+				}
+			}	
+			tnidx += 1;
 		}		
 	}
 	pub fn apply_key_push_2(&self, nodes: &Vec::<PathNode>, km: &KeyMove) -> PathMap { 	// after we complete a map, we need to take a key move and start again	
@@ -208,6 +192,7 @@ impl PathMap {
 		}
 
 		map_b.level.set_human_pos(&np);				// move human
+		map_b.human_pos = np;
 		
 		map_b.path = backtrace_moves2(nodes, km.pni as usize, map_b.path);
 		map_b.path = map_b.path.push(km.move_dir as u8);
@@ -239,6 +224,7 @@ impl PathMap {
 		// new human point
 		let np = nodes[km.pni as usize].pt.add_dir(&km.move_dir);
 		map_b.level.set_human_pos(&np);
+		map_b.human_pos = np;
 
 		map_b.path = backtrace_moves2(nodes, km.pni as usize, map_b.path);
 		map_b.path = map_b.path.push(km.move_dir as u8);
@@ -268,6 +254,7 @@ impl PathMap {
 		}
 
 		map_b.level.set_human_pos(&np);				// move human
+		map_b.human_pos = np;
 		
 		map_b.path = pnm.backtrace_moves(km.pni as usize, map_b.path);
 		map_b.path = map_b.path.push(km.move_dir as u8);
@@ -299,6 +286,7 @@ impl PathMap {
 		// new human point
 		let np = pnm.nodes[km.pni as usize].pt.add_dir(&km.move_dir);
 		map_b.level.set_human_pos(&np);
+		map_b.human_pos = np;
 
 		map_b.path = pnm.backtrace_moves(km.pni as usize, map_b.path);
 		map_b.path = map_b.path.push(km.move_dir as u8);
@@ -307,104 +295,109 @@ impl PathMap {
 	}
 	pub fn complete_map_solve(&self, base_level: &Level) -> PathNodeMap {
 		let mut pnm = self.to_pnm();					// we want complete_map to clone from self
-		let mut tail_nodes = StackStack16x64::new(); 
-		let mut new_tail_nodes = StackStack16x64::new(); 	// somewhere to store new tail nodes
-		tail_nodes.push(0);
-		while tail_nodes.len() != 0 {					// check if map is complete
-			for idx in 0..tail_nodes.len() {							// for each tail node
-				let tnidx = tail_nodes.stack[idx]; 
-				let tnode = pnm.nodes[tnidx as usize];
-				let pt = tnode.pt;									
-				'loop_moves: for movedir in ALLMOVES.iter() {			// for each possible move
-					let npt = pt.add_dir(&movedir);						// what is in this direction? let's find out
-					if !base_level.vector_in_bounds(&npt) { continue; }
-					if self.level.is_boxx_at_pt(&npt) {
-						// What's past the boxx? We can push into Space and Hole.
-						let bnpt = pt.add_dir2(&movedir);
-						let nobj = self.level.get_obj_at_pt_nohuman_checked(&bnpt, base_level);
-						if nobj == Obj::Space || nobj == Obj::Hole {
-							// yep, its a keymove, save key move.. but before we do, make sure it isn't a double boxx situation or in our noboxx list
-							if !base_level.in_noboxx_pts(&bnpt)  && !self.double_boxx_situation(pt,*movedir,base_level) {
-								let km = KeyMove {
-									pni: tnidx,
-									move_dir: *movedir,
-								};
-								pnm.key_moves.push(km);
-							}
-						} 
-					} else if base_level.get_obj_at_pt(&npt) != Obj::Wall {											
-						// first check this point isn't already in our list!!!						
-						for n in &pnm.nodes {
-							if n.pt == npt { continue 'loop_moves; }		// This is a hot spot 9.88%
+		let mut tnidx: u16 = 0;
+		while (tnidx as usize) < pnm.nodes.len() {				// nodes are a queue: each one is a tail node, until we've looked around it
+			let tnode = pnm.nodes[tnidx as usize];
+			let pt = tnode.pt;									
+			'loop_moves: for movedir in ALLMOVES.iter() {			// for each possible move
+				let npt = pt.add_dir(&movedir);						// what is in this direction? let's find out
+				if !base_level.vector_in_bounds(&npt) { continue; }
+				if self.level.is_boxx_at_pt(&npt) {
+					// What's past the boxx? We can push into Space and Hole.
+					let bnpt = pt.add_dir2(&movedir);
+					let nobj = self.level.get_obj_at_pt_nohuman_checked(&bnpt, base_level);
+					if nobj == Obj::Space || nobj == Obj::Hole {
+						// yep, its a keymove, save key move.. but before we do, make sure it isn't a double boxx situation or in our noboxx list
+						if !base_level.in_noboxx_pts(&bnpt)  && !self.double_boxx_situation(pt,*movedir,base_level) {
+							let km = KeyMove {
+								pni: tnidx,
+								move_dir: *movedir,
+							};
+							pnm.key_moves.push(km);
 						}
-
-						// yep, we can move here, make a new tail node
-						let pn = PathNode {
-							pt: npt.clone(),
-							move_taken: Some(*movedir),
-							prev_node_idx: tnidx as u16,
-						};
-						new_tail_nodes.push(pnm.nodes.len() as u16);
-						pnm.nodes.push(pn);
+					} 
+				} else if base_level.get_obj_at_pt(&npt) != Obj::Wall {											
+					// first check this point isn't already in our list!!!						
+					for n in &pnm.nodes {
+						if n.pt == npt { continue 'loop_moves; }		// This is a hot spot 9.88%
 					}
-				}	
-			}
-	
-			// move new_tail_nodes to tail_nodes
-			tail_nodes.clone_from(&new_tail_nodes);
-			new_tail_nodes.clear();
+
+					// yep, we can move here, make a new tail node
+					let pn = PathNode {
+						pt: npt.clone(),
+						move_taken: Some(*movedir),
+						prev_node_idx: tnidx,
+					};
+					pnm.nodes.push(pn);
+				}
+			}	
+			tnidx += 1;
 		}
 		pnm
 	}
 	pub fn complete_map_unsolve(&self, base_level: &Level) -> PathNodeMap {
 		let mut pnm = self.to_pnm();					// we want complete_map to clone from self
-		let mut tail_nodes = StackStack16x64::new(); 
-		let mut new_tail_nodes = StackStack16x64::new();
-		tail_nodes.push(0);		
-		while tail_nodes.len() != 0 {					// check if map is complete
-			for idx in 0..tail_nodes.len() {
-				let tnidx = tail_nodes.stack[idx];
-				let tnode = pnm.nodes[tnidx as usize];
-				let pt = tnode.pt;									
-				'loop_moves: for movedir in ALLMOVES.iter() {							// for each possible move
-					let npt = pt.add_dir(&movedir);							// what is in this direction? let's find out	
-					if !base_level.vector_in_bounds(&npt) { continue; }
-					if self.level.is_boxx_at_pt(&npt) {
-						// What's in our reverse direction? We can pull into Space and Hole.
-						let bnpt = pt.add_dir(&movedir.reverse());
-						let nobj = self.level.get_obj_at_pt_nohuman_checked(&bnpt, base_level);
-						if nobj == Obj::Space || nobj == Obj::Hole {
-							// yep, its a keypull, save key move.. 
-							let km = KeyMove {
-								pni: tnidx,
-								move_dir: movedir.reverse(),
-							};
-							pnm.key_moves.push(km);
-						}
-					} else if base_level.get_obj_at_pt(&npt) != Obj::Wall {
-						// first check this point isn't already in our list!!!						
-						for n in pnm.nodes.iter() {
-							if n.pt == npt { continue 'loop_moves; }
-						}
-						
-						// yep, we can move here, make a new tail node
-						let pn = PathNode {
-							pt: npt.clone(),
-							move_taken: Some(*movedir),
-							prev_node_idx: tnidx as u16,
+		let mut tnidx: u16 = 0;
+		while (tnidx as usize) < pnm.nodes.len() {				// nodes are a queue: each one is a tail node, until we've looked around it
+			let tnode = pnm.nodes[tnidx as usize];
+			let pt = tnode.pt;									
+			'loop_moves: for movedir in ALLMOVES.iter() {							// for each possible move
+				let npt = pt.add_dir(&movedir);							// what is in this direction? let's find out	
+				if !base_level.vector_in_bounds(&npt) { continue; }
+				if self.level.is_boxx_at_pt(&npt) {
+					// What's in our reverse direction? We can pull into Space and Hole.
+					let bnpt = pt.add_dir(&movedir.reverse());
+					let nobj = self.level.get_obj_at_pt_nohuman_checked(&bnpt, base_level);
+					if nobj == Obj::Space || nobj == Obj::Hole {
+						// yep, its a keypull, save key move.. 
+						let km = KeyMove {
+							pni: tnidx,
+							move_dir: movedir.reverse(),
 						};
-						new_tail_nodes.push(pnm.nodes.len() as u16);
-						pnm.nodes.push(pn);
+						pnm.key_moves.push(km);
 					}
-				}	
-			}
-
-			// move new_tail_nodes to tail_nodes
-			tail_nodes.clone_from(&new_tail_nodes);
-			new_tail_nodes.clear();
+				} else if base_level.get_obj_at_pt(&npt) != Obj::Wall {
+					// first check this point isn't already in our list!!!						
+					for n in pnm.nodes.iter() {
+						if n.pt == npt { continue 'loop_moves; }
+					}
+					
+					// yep, we can move here, make a new tail node
+					let pn = PathNode {
+						pt: npt.clone(),
+						move_taken: Some(*movedir),
+						prev_node_idx: tnidx,
+					};
+					pnm.nodes.push(pn);
+				}
+			}	
+			tnidx += 1;
 		}		
 		pnm
 	}
+	pub fn canonicalise_human(&mut self, base_level: &Level) {
+		// when only pushes count, it doesn't matter where the human is within the area they can walk around
+		// so compare maps as if the human were in the top-left cell of that area
+		let mut seen = vec![false; base_level.w as usize * base_level.h as usize];
+		let mut todo = Vec::<Vector>::with_capacity(64);
+		let mut best = self.human_pos;
+		seen[best.to_index(base_level.w)] = true;
+		todo.push(best);
+		while let Some(pt) = todo.pop() {
+			if pt.to_index(base_level.w) < best.to_index(base_level.w) {
+				best = pt;
+			}
+			for movedir in ALLMOVES.iter() {
+				let npt = pt.add_dir(movedir);
+				if !base_level.vector_in_bounds(&npt) || seen[npt.to_index(base_level.w)] { continue; }
+				if base_level.get_obj_at_pt(&npt) != Obj::Wall && !self.level.is_boxx_at_pt(&npt) {
+					seen[npt.to_index(base_level.w)] = true;
+					todo.push(npt);
+				}
+			}
+		}
+		self.level.set_human_pos(&best);
+	}
 	pub fn double_boxx_situation(&self, human_pos: Vector, pushdir: Move, base_level: &Level) -> bool {
 		// checks for a situation where we would be pushing the boxx next to another boxx against a wall and getting ourselves stuck
 		//         a = anything, h = human, pushdir = right, * = boxx, # = wall, ' ' = space, only need row 1 or 3 not both
@@ -466,57 +459,40 @@ impl PathNodeMap {
 		}
 		nmaps
 	}
-	pub fn backtrace_moves(&self, pni: usize, spath: TreeNodeRef) -> TreeNodeRef {		// 5.5, 2.9
-		let mut path = StackStack8x64::new();
-		// start at pn and work backwards
-		let mut pnr = &self.nodes[pni];
-		loop {
-			if pnr.move_taken.is_some() {
-				path.push(pnr.move_taken.unwrap() as u8);
-				if pnr.prev_node_idx == 0 {
-					let m = &self.nodes[0].move_taken;
-					if m.is_some() { path.push(m.unwrap() as u8); }
-					break;
-				}
-				pnr = &self.nodes[pnr.prev_node_idx as usize];
-			} else {
-				break;
-			}
-		}
-		
-		let mut spath2 = spath;
-		for i in 1..=path.next {
-			let rev = path.next - i;
-			spath2 = spath2.push(path.stack[rev]);
-		}
-		spath2
+	pub fn backtrace_moves(&self, pni: usize, spath: TreeNodeRef) -> TreeNodeRef {
+		backtrace_moves2(&self.nodes, pni, spath)
 	}
 }
 
 
 pub fn backtrace_moves2(nodes: &Vec::<PathNode>, pni: usize, spath: TreeNodeRef) -> TreeNodeRef {		// 5.5, 2.9
-	let mut path = StackStack8x64::new();
-	// start at pn and work backwards
-	let mut pnr = nodes[pni];
-	loop {
-		if pnr.move_taken.is_some() {
-			path.push(pnr.move_taken.unwrap() as u8);
-			if pnr.prev_node_idx == 0 {
-				let m = nodes[0].move_taken;
-				if m.is_some() { path.push(m.unwrap() as u8); }
-				break;
-			}
-			pnr = nodes[pnr.prev_node_idx as usize];
-		} else {
-			break;
-		}
+	// start at pn and work backwards, counting the moves, so we can write them into the path front to back
+	let mut len = 0;
+	let mut idx = pni;
+	while idx != 0 {
+		len += 1;
+		idx = nodes[idx].prev_node_idx as usize;
+	}
+
+	// short walks fit on the stack, long walks (in big levels) go on the heap
+	let mut small_path = [0_u8; 64];
+	let mut big_path = Vec::<u8>::new();
+	let path: &mut [u8] = if len <= small_path.len() {
+		&mut small_path[0..len]
+	} else {
+		big_path.resize(len, 0);
+		&mut big_path
+	};
+
+	let mut idx = pni;
+	for i in (0..len).rev() {
+		path[i] = nodes[idx].move_taken.unwrap() as u8;
+		idx = nodes[idx].prev_node_idx as usize;
 	}
 	
 	let mut spath2 = spath;
-	for i in 1..=path.next {
-		let rev = path.next - i;
-		spath2 = spath2.push(path.stack[rev]);
+	for m in path.iter() {
+		spath2 = spath2.push(*m);
 	}
 	spath2
 }
-
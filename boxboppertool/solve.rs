@@ -2,15 +2,16 @@
 //
 // solve.rs: solve a sokoban-style level
 
-use boxbopperbase::level::{Level,CmpData};
+use boxbopperbase::level::{Level};
 use boxbopperbase::time::{get_time_ms};
 
 use crate::pathnodemap::{PathMap};
+use crate::astar::{solve_level_astar};
+use crate::transtable::{TransTable};
+use crate::spill::{SpillDir,RunMerger,Frontier};
 //use crate::shrunkpath::{TreeNode};
 
-use rayon::prelude::*;
-use std::rc::Rc;
-use std::collections::{BTreeMap};
+use std::path::PathBuf;
 use std::cmp::Ordering;
 use itertools::Itertools;
 use bevy_tasks::{TaskPool,TaskPoolBuilder};
@@ -172,23 +173,100 @@ pub fn vec_unslice(mut from: Vec::<Vec::<PathMap>>) -> Vec::<PathMap> {
 #[derive(Clone)]
 pub struct Solution {
 	pub moves: u16,
+	pub pushes: u16,
 	pub depth: u16,
 	pub secs: f64,
 	pub path: String
 }
 
+// Which search to use
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Algo { Bfs, AStar }
+
+impl std::str::FromStr for Algo {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Algo, String> {
+		match s {
+			"bfs"   => Ok(Algo::Bfs),
+			"astar" => Ok(Algo::AStar),
+			_ => Err(format!("Unknown algo {}", s)),
+		}
+	}
+}
+
+impl std::fmt::Display for Algo {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		let s = match self {
+			Algo::Bfs   => "bfs",
+			Algo::AStar => "astar",
+		};
+		f.pad(s)
+	}
+}
+
+// What makes one solution better than another
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Metric { Moves, Pushes, PushesThenMoves }
+
+impl std::str::FromStr for Metric {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Metric, String> {
+		match s {
+			"moves"             => Ok(Metric::Moves),
+			"pushes"            => Ok(Metric::Pushes),
+			"pushes_then_moves" => Ok(Metric::PushesThenMoves),
+			_ => Err(format!("Unknown metric {}", s)),
+		}
+	}
+}
+
+impl std::fmt::Display for Metric {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		let s = match self {
+			Metric::Moves           => "moves",
+			Metric::Pushes          => "pushes",
+			Metric::PushesThenMoves => "pushes_then_moves",
+		};
+		f.pad(s)
+	}
+}
+
+impl Solution {
+	pub fn score(&self, metric: Metric) -> (u16,u16) {
+		// what the metric compares, lowest is best
+		match metric {
+			Metric::Moves           => (self.moves, 0),
+			Metric::Pushes          => (self.pushes, 0),
+			Metric::PushesThenMoves => (self.pushes, self.moves),
+		}
+	}
+}
+
+pub fn solve_level_algo(algo: Algo, metric: Metric, base_level_in: &Level, max_moves_requested: u16, max_maps: usize, table_mem: usize, spill_dir: &str, verbosity: u32, num_threads: usize) -> Option<Solution> {
+	match algo {
+		Algo::Bfs   => solve_level(base_level_in, metric, max_moves_requested, max_maps, table_mem, spill_dir, verbosity, num_threads),
+		Algo::AStar => solve_level_astar(base_level_in, metric, max_moves_requested, max_maps, verbosity),		// single threaded
+	}
+}
+
 
-pub fn solve_level(base_level_in: &Level, max_moves_requested: u16, max_maps: usize, verbosity: u32, num_threads: usize) -> Option<Solution> {
+pub fn solve_level(base_level_in: &Level, metric: Metric, max_moves_requested: u16, max_maps: usize, table_mem: usize, spill_dir: &str, verbosity: u32, num_threads: usize) -> Option<Solution> {
+	// each depth is one more push, so the first depth with a solution has the fewest pushes
+	let by_pushes = metric != Metric::Moves;
+	let canonical = metric == Metric::Pushes;		// moves don't matter at all, so the human can be anywhere they can walk to
 	let mut max_moves = max_moves_requested+1;
 	let base_level1 = base_level_in.clear_human_cloned();
 	let base_map = PathMap::new_from_level(&base_level1);
-	let _path_root = base_map.path.clone(); 	// we have to keep reference so the root doesn't dissappear
+	let path_root = base_map.path.clone(); 	// we have to keep reference so the root doesn't dissappear, spilled paths are read back from it too
 	let base_level = base_level1.clear_boxxes_cloned();
-	let mut non_contenders = BTreeMap::<CmpData,u16>::new();
+	let table = TransTable::new(table_mem, num_threads, &base_level);		// every map we've expanded, with the fewest moves to get there
 
-	let mut bvec = Vec::new();
-	bvec.push(base_map);
-	let mut mapsr = Rc::new(bvec);
+	let mut frontier = Frontier::Mem(vec![base_map]);
+	let mut spill = if spill_dir.is_empty() {
+		None
+	} else {
+		Some(SpillDir::new(spill_dir).expect("Failed to create spill directory"))
+	};
 	
 	let pool = TaskPoolBuilder::new()
 		.thread_name("Box Bopper Tool Thread Pool".to_string())
@@ -208,75 +286,131 @@ pub fn solve_level(base_level_in: &Level, max_moves_requested: u16, max_maps: us
 	while depth < max_moves {
 		if verbosity > 0 { println!("-- Depth {:>2} --", depth); }
 
-		// Check for level complete / having solution
-		if verbosity > 1 { println!("solution check..."); }
-		mapsr.iter().filter(|m| m.level.have_win_condition(&base_level)).for_each(|m| {
-			let len = m.path.len();
-			if len < max_moves {
-				have_solution = true;
-				max_moves = len;
-				best_solution.depth = depth;
-				best_solution.s = format!("{}", m.path.to_string());
-				if verbosity > 0 { 
-					println!("-- Solution found in {} moves --", len);
-					m.path.print_tree_size();
+		// Maps for the next depth, and any we've had to spill to disk
+		let mut next = Vec::<PathMap>::new();
+		let mut next_runs = Vec::<PathBuf>::new();
+
+		// This depth comes all at once from memory, or a chunk at a time from disk
+		while let Some(mut mapsr) = frontier.next_chunk((max_maps/2).max(1)).expect("Failed to read spill files") {
+			// Check for level complete / having solution
+			if verbosity > 1 { println!("solution check..."); }
+			mapsr.iter().filter(|m| m.level.have_win_condition(&base_level)).for_each(|m| {
+				let len = m.path.len();
+				if len < max_moves {
+					have_solution = true;
+					max_moves = len;
+					best_solution.depth = depth;
+					best_solution.s = format!("{}", m.path.to_string());
+					if verbosity > 0 { 
+						println!("-- Solution found in {} moves --", len);
+						m.path.print_tree_size();
+					}
 				}
+			});
+			if have_solution && by_pushes {
+				continue;		// check the rest of this depth for solutions with fewer moves, but don't go any deeper
 			}
-		});
 
-		// We have to store number of moves, because higher depth can have less moves
-		if verbosity > 1 { println!("adding {} old maps to non-contenders...", mapsr.len()); }
-		if non_contenders.len() < max_maps * 4 {
-			//mapsr.par_iter().for_each(|m| { non_contenders.insert(m.level.cmp_data, m.path.len()); });
-			non_contenders.par_extend(mapsr.par_iter().map(|m| (m.level.cmp_data, m.path.len()) ));
-		} else {
-			if verbosity > 0 { println!("--- Old maps hit max_maps limit, not adding more ---"); }				// Performance will drag after this point, as we'll probably end up repeating moves
-		}
-
-		// Perform next key moves
-		if verbosity > 1 { println!("performing next key moves..."); }
-		let mut maps = task_splitter(&pool, num_threads, &mapsr, |maps_read: &[PathMap], mut maps_write: &mut Vec::<PathMap>| {
-			maps_read.iter().for_each(|m| m.complete_solve_2(&base_level, &mut maps_write));		// perform next key moves
-			maps_write.retain(|m| m.path.len() < max_moves);										// filter out long moves
-		});
+			// Add the maps we're about to expand to the table (or shorten them), skipping any we've already expanded at
+			// the same or lower cost. Only maps that get expanded go in, so maps thrown out by a purge can be found again.
+			let pushes = if by_pushes { (depth as u32) << 16 } else { 0 };
+			mapsr = task_splitter_mut(&pool, num_threads, mapsr, |maps: &mut [PathMap]| {
+				for m in maps {
+					let cost = pushes | m.path.len() as u32;
+					if let Some(best) = table.insert_min(&m.level.cmp_data, cost) {
+						if best <= cost {
+							m.flag = true;
+						}
+					}
+				}
+			});
+			mapsr.retain(|m| !m.flag);
+			if table.is_full() && verbosity > 0 {
+				println!("--- Table hit table_mem limit, not adding more ---");		// Performance will drag after this point, as we'll probably end up repeating moves
+			}
 
-		// Sort and deduplicate
-		if depth >= 2 { 
-			if verbosity > 1 { println!("deduping: before {:>7}", maps.len()); }
-			maps = task_splitter_sort(&pool, num_threads, maps);
-			if verbosity > 1 { println!("deduping: after  {:>7}", maps.len()); }
-		} 
-
-		// Remove from maps anything that is in non_contenders AND our path is equal/longer. (Our shorter paths will be updated/added at the next loop)
-		if verbosity > 1 { println!("deduping using n-c: before {:>7}", maps.len()); }		
-		maps = task_splitter_mut(&pool, num_threads, maps, |maps: &mut [PathMap]| {
-			for m in maps {
-				let v = non_contenders.get(&m.level.cmp_data);
-				if v.is_some() {
-					if *v.unwrap() <= m.path.len() {
-						m.flag = true;
+			// Perform next key moves
+			if verbosity > 1 { println!("performing next key moves..."); }
+			let mut maps = task_splitter(&pool, num_threads, &mapsr, |maps_read: &[PathMap], mut maps_write: &mut Vec::<PathMap>| {
+				maps_read.iter().for_each(|m| m.complete_solve_2(&base_level, &mut maps_write));		// perform next key moves
+				if canonical {
+					maps_write.iter_mut().for_each(|m| m.canonicalise_human(&base_level));
+				}
+				maps_write.retain(|m| m.path.len() < max_moves);										// filter out long moves
+			});
+
+			// Sort and deduplicate
+			if depth >= 2 { 
+				if verbosity > 1 { println!("deduping: before {:>7}", maps.len()); }
+				maps = task_splitter_sort(&pool, num_threads, maps);
+				if verbosity > 1 { println!("deduping: after  {:>7}", maps.len()); }
+			} 
+
+			// Remove from maps anything that is in the table AND our path is equal/longer. (They go in the table when they're expanded)
+			// We have to store number of moves, because higher depth can have less moves.
+			// When counting pushes, the pushes come first, so anything from an earlier depth always goes
+			if verbosity > 1 { println!("deduping using table: before {:>7}", maps.len()); }
+			let pushes = if by_pushes { (depth as u32 + 1) << 16 } else { 0 };
+			maps = task_splitter_mut(&pool, num_threads, maps, |maps: &mut [PathMap]| {
+				for m in maps {
+					if let Some(best) = table.get(&m.level.cmp_data) {
+						if best <= pushes | m.path.len() as u32 {
+							m.flag = true;
+						}
 					}
 				}
+			});
+			maps.retain(|m| !m.flag);
+			if verbosity > 1 {
+				println!("deduping using table: after  {:>7}", maps.len());
+				println!("table: {} maps, {} MB", table.len(), table.mem_bytes() / (1024*1024));
 			}
-		});
-		maps.retain(|m| !m.flag);
-		if verbosity > 1 { println!("deduping using n-c: after  {:>7}", maps.len()); }
 
-		// Check if we've exhausted the search space
-		if maps.len() == 0 {
-			if verbosity > 0 { println!("-- No more maps to check --"); }
+			if next.is_empty() {
+				next = maps;
+			} else {
+				next.append(&mut maps);
+			}
+
+			// Check if we've hit max_maps (our memory/resource limit)
+			if next.len() > max_maps {
+				println!("--- Hit maximum maps ({}) ---",max_maps);
+				match spill.as_mut() {
+					Some(sp) => {
+						if verbosity > 0 { println!("--- Spilling {} maps to disk ---", next.len()); }
+						next = task_splitter_sort(&pool, num_threads, next);
+						next_runs.push(sp.write_run(&next).expect("Failed to write spill file"));
+						next.clear();
+					},
+					None => {
+						println!("--- Purging lots of maps, solutions may be thrown out (set spill_dir to keep them) ---");
+						next.truncate(max_maps/2);
+					},
+				}
+			}
+		}
+		if have_solution && by_pushes {
 			break;
 		}
 
-		// Check if we've hit max_maps (our memory/resource limit)
-		if maps.len() > max_maps {
-			println!("--- Hit maximum maps ({}) ---",max_maps);
-			println!("--- Purging lots of maps, solutions may be thrown out ---");
-			maps.truncate(max_maps/2);
+		// Check if we've exhausted the search space
+		if next.is_empty() && next_runs.is_empty() {
+			if verbosity > 0 { println!("-- No more maps to check --"); }
+			break;
 		}
 
 		// Loop and check the next depth
-		mapsr = Rc::new(maps);
+		if next_runs.is_empty() {
+			frontier = Frontier::Mem(next);
+		} else {
+			// spill the rest too, so they can all be merged together
+			if !next.is_empty() {
+				next = task_splitter_sort(&pool, num_threads, next);
+				next_runs.push(spill.as_mut().unwrap().write_run(&next).expect("Failed to write spill file"));
+			}
+			if verbosity > 1 { println!("merging {} runs from disk...", next_runs.len()); }
+			frontier = Frontier::Disk(RunMerger::new(next_runs, &path_root).expect("Failed to read spill files"));
+		}
 		depth += 1;
 	}
 
@@ -284,11 +418,12 @@ pub fn solve_level(base_level_in: &Level, max_moves_requested: u16, max_maps: us
 		let sol = best_solution;		
 		if verbosity > 0 { 
 			println!("-- Best solution --");
-			println!("Solution in {} moves: {}",max_moves, sol.s);
+			println!("Solution in {} moves, {} pushes: {}",max_moves, sol.depth, sol.s);
 		}
 		return Some(Solution {
 			secs: (get_time_ms() - msecs0) / 1000_f64,
 			moves: max_moves,
+			pushes: sol.depth,			// each key move is one push
 			depth: sol.depth,
 			path: sol.s.to_string(),
 		});
@@ -0,0 +1,309 @@
+// boxboppertool Copyright 2020-2021 David Atkinson
+//
+// spill.rs: keep maps on disk when there are too many to hold in memory
+//
+// Maps are sorted (like task_splitter_sort does) and written out as runs. At the next depth the runs are merged
+// back together, again keeping only the shortest path to each map, and read back in chunks.
+// Only the maps go to disk, the path tree stays in memory. Reading a path back finds the nodes already in the tree.
+
+use boxbopperbase::level::{SpLevel,CmpData,BitBlocks};
+use boxbopperbase::vector::{Vector,Move};
+
+use crate::pathnodemap::{PathMap};
+use crate::shrunkpath::{TreeNodeRef};
+
+use std::fs::{File};
+use std::io::{self,Read,Write,BufRead,BufReader,BufWriter};
+use std::path::{Path,PathBuf};
+use std::rc::Rc;
+use std::cell::RefCell;
+use itertools::Itertools;
+
+// A directory of our own for the run files, removed when we're done
+pub struct SpillDir {
+	dir: PathBuf,
+	count: usize,
+}
+
+impl SpillDir {
+	pub fn new(parent: &str) -> io::Result<SpillDir> {
+		let dir = Path::new(parent).join(format!("boxbopper-spill-{}", std::process::id()));
+		std::fs::create_dir_all(&dir)?;
+		Ok(SpillDir { dir, count: 0 })
+	}
+	pub fn write_run(&mut self, maps: &[PathMap]) -> io::Result<PathBuf> {
+		// maps should already be sorted and deduped
+		let filename = self.dir.join(format!("run-{}.bin", self.count));
+		self.count += 1;
+		let mut f = BufWriter::new(File::create(&filename)?);
+		for m in maps.iter() {
+			write_map(&mut f, m)?;
+		}
+		f.flush()?;
+		Ok(filename)
+	}
+}
+
+impl Drop for SpillDir {
+	fn drop(&mut self) {
+		let _ = std::fs::remove_dir_all(&self.dir);
+	}
+}
+
+fn write_i16(f: &mut impl Write, v: i16) -> io::Result<()> { f.write_all(&v.to_le_bytes()) }
+fn write_u16(f: &mut impl Write, v: u16) -> io::Result<()> { f.write_all(&v.to_le_bytes()) }
+
+fn read_i16(f: &mut impl Read) -> io::Result<i16> {
+	let mut b = [0_u8; 2];
+	f.read_exact(&mut b)?;
+	Ok(i16::from_le_bytes(b))
+}
+
+fn read_u16(f: &mut impl Read) -> io::Result<u16> {
+	let mut b = [0_u8; 2];
+	f.read_exact(&mut b)?;
+	Ok(u16::from_le_bytes(b))
+}
+
+// One map is: w, h, human x, y (real), human x, y (cmp_data), block count, blocks, depth, path length, path (4 moves a byte)
+fn write_map(f: &mut impl Write, m: &PathMap) -> io::Result<()> {
+	write_i16(f, m.level.w)?;
+	write_i16(f, m.level.h)?;
+	write_i16(f, m.human_pos.0 as i16)?;
+	write_i16(f, m.human_pos.1 as i16)?;
+	write_i16(f, m.level.cmp_data.human_x)?;
+	write_i16(f, m.level.cmp_data.human_y)?;
+	write_u16(f, m.level.cmp_data.blocks.len() as u16)?;
+	for b in m.level.cmp_data.blocks.iter() {
+		f.write_all(&b.to_le_bytes())?;
+	}
+	write_u16(f, m.depth)?;
+	let path = m.path.to_path();
+	write_u16(f, path.len() as u16)?;
+	for moves in path.chunks(4) {
+		let byte = moves.iter().enumerate().fold(0_u8, |acc, (i, mv)| acc | ((*mv as u8) << (2*i)));
+		f.write_all(&[byte])?;
+	}
+	Ok(())
+}
+
+fn read_map(f: &mut impl Read, root: &TreeNodeRef) -> io::Result<PathMap> {
+	let w = read_i16(f)?;
+	let h = read_i16(f)?;
+	let human_pos = Vector(read_i16(f)? as i32, read_i16(f)? as i32);
+	let human_x = read_i16(f)?;
+	let human_y = read_i16(f)?;
+	let num_blocks = read_u16(f)? as usize;
+	let mut blocks = BitBlocks::new(w as usize * h as usize);
+	if blocks.len() != num_blocks {
+		return Err(io::Error::new(io::ErrorKind::InvalidData, "spilled map has the wrong number of blocks"));
+	}
+	for block in blocks.iter_mut() {
+		let mut b = [0_u8; 8];
+		f.read_exact(&mut b)?;
+		*block = u64::from_le_bytes(b);
+	}
+	let depth = read_u16(f)?;
+	let path_len = read_u16(f)? as usize;
+	let mut bytes = vec![0_u8; path_len.div_ceil(4)];
+	f.read_exact(&mut bytes)?;
+	let path: Vec::<Move> = (0..path_len).map(|i| Move::from_u8_unchecked((bytes[i/4] >> (2*(i%4))) & 0x03)).collect();
+	Ok(PathMap {
+		level: SpLevel { w, h, cmp_data: CmpData { human_x, human_y, blocks } },
+		human_pos,
+		path: root.clone().push_path(&path),		// the nodes are still in the tree, so this doesn't add any
+		depth,
+		flag: false,
+	})
+}
+
+type ErrorSlot = Rc<RefCell<Option<io::Error>>>;
+
+struct RunReader {
+	f: BufReader<File>,
+	root: TreeNodeRef,
+	error: ErrorSlot,			// shared with the RunMerger, which reports it
+}
+
+impl Iterator for RunReader {
+	type Item = PathMap;
+	fn next(&mut self) -> Option<PathMap> {
+		// the run can only end between maps, running out part way through one means the file is truncated
+		let res = match self.f.fill_buf() {
+			Ok([]) => return None,
+			Ok(_) => read_map(&mut self.f, &self.root),
+			Err(e) => Err(e),
+		};
+		match res {
+			Ok(m) => Some(m),
+			Err(e) => {
+				// end this run, and keep the first error for the merger
+				self.error.borrow_mut().get_or_insert(e);
+				None
+			},
+		}
+	}
+}
+
+fn pm_lt(a: &PathMap, b: &PathMap) -> bool {
+	// same order as task_splitter_sort: by map, then shortest path first
+	match a.level.cmp_data.cmp(&b.level.cmp_data) {
+		std::cmp::Ordering::Equal => a.path.len() < b.path.len(),
+		ord => ord == std::cmp::Ordering::Less,
+	}
+}
+
+// All the runs for one depth, merged, with the shortest path to each map
+pub struct RunMerger {
+	files: Vec::<PathBuf>,
+	maps: Box<dyn Iterator<Item = PathMap>>,
+	error: ErrorSlot,
+}
+
+impl RunMerger {
+	pub fn new(files: Vec::<PathBuf>, root: &TreeNodeRef) -> io::Result<RunMerger> {
+		let error = ErrorSlot::default();
+		let mut readers = Vec::<RunReader>::with_capacity(files.len());
+		for filename in files.iter() {
+			readers.push(RunReader { f: BufReader::new(File::open(filename)?), root: root.clone(), error: error.clone() });
+		}
+		let maps = readers.into_iter().kmerge_by(pm_lt).dedup_by(|a,b| a.level.cmp_data == b.level.cmp_data);
+		Ok(RunMerger { files, maps: Box::new(maps), error })
+	}
+	pub fn next_chunk(&mut self, size: usize) -> io::Result<Vec::<PathMap>> {
+		let chunk: Vec::<PathMap> = self.maps.by_ref().take(size).collect();
+		match self.error.borrow_mut().take() {
+			Some(e) => Err(e),
+			None => Ok(chunk),
+		}
+	}
+}
+
+impl Drop for RunMerger {
+	fn drop(&mut self) {
+		for filename in self.files.iter() {
+			let _ = std::fs::remove_file(filename);
+		}
+	}
+}
+
+// The maps for one depth, in memory, or on disk
+pub enum Frontier {
+	Mem(Vec::<PathMap>),
+	Disk(RunMerger),
+	Done,
+}
+
+impl Frontier {
+	pub fn next_chunk(&mut self, size: usize) -> io::Result<Option<Vec::<PathMap>>> {
+		// maps in memory come all at once, maps on disk come size at a time
+		match std::mem::replace(self, Frontier::Done) {
+			Frontier::Mem(maps) => Ok(Some(maps)),
+			Frontier::Disk(mut merger) => {
+				let chunk = merger.next_chunk(size)?;
+				if chunk.is_empty() {
+					return Ok(None);
+				}
+				*self = Frontier::Disk(merger);
+				Ok(Some(chunk))
+			},
+			Frontier::Done => Ok(None),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use boxbopperbase::level::Level;
+
+	fn maps_two_pushes_deep(level: &Level) -> (Level, TreeNodeRef, Vec::<PathMap>) {
+		// every map two pushes from the start, with duplicates, and the root their paths hang off
+		let base_level1 = level.clear_human_cloned();
+		let base_map = PathMap::new_from_level(&base_level1);
+		let root = base_map.path.clone();
+		let base_level = base_level1.clear_boxxes_cloned();
+		let mut first = Vec::<PathMap>::new();
+		base_map.complete_solve_2(&base_level, &mut first);
+		let mut second = Vec::<PathMap>::new();
+		first.iter().for_each(|m| m.complete_solve_2(&base_level, &mut second));
+		(base_level, root, second)
+	}
+
+	fn sort_dedup(mut maps: Vec::<PathMap>) -> Vec::<PathMap> {
+		maps.sort_by(|a,b| if pm_lt(a,b) { std::cmp::Ordering::Less } else if pm_lt(b,a) { std::cmp::Ordering::Greater } else { std::cmp::Ordering::Equal });
+		maps.dedup_by(|a,b| a.level.cmp_data == b.level.cmp_data);
+		maps
+	}
+
+	#[test]
+	fn write_and_merge_runs() {
+		let level = Level::from_str("#########\n#   O   #\n# *&* O #\n#   *   #\n#  O    #\n#########\n").unwrap();
+		let (_, root, maps) = maps_two_pushes_deep(&level);
+		let expected = sort_dedup(maps);
+		let n = expected.len();
+		assert!(n > 6);
+
+		let parent = std::env::temp_dir().join("boxbopper-spill-test-merge");
+		let mut spill = SpillDir::new(parent.to_str().unwrap()).unwrap();
+
+		// one run reads back exactly as it was written
+		let run = spill.write_run(&expected).unwrap();
+		let mut merger = RunMerger::new(vec![run.clone()], &root).unwrap();
+		let back = merger.next_chunk(usize::MAX).unwrap();
+		assert_eq!(back.len(), expected.len());
+		for (a,b) in back.iter().zip(expected.iter()) {
+			assert!(a.level.cmp_data == b.level.cmp_data);
+			assert_eq!((a.level.w, a.level.h, a.human_pos, a.depth), (b.level.w, b.level.h, b.human_pos, b.depth));
+			assert_eq!(a.path.to_path(), b.path.to_path());
+		}
+		std::mem::drop(merger);
+		assert!(!run.exists());
+
+		// overlapping runs merge into one sorted list, with the shortest path to each map, a chunk at a time
+		let mut a = expected[..n*2/3].to_vec();
+		a[n/3..].iter_mut().for_each(|m| m.path = m.path.clone().push(Move::Up as u8));		// a longer way to the same maps
+		let b = expected[n/3..].to_vec();
+		let runs = vec![spill.write_run(&a).unwrap(), spill.write_run(&b).unwrap()];
+		let mut merger = RunMerger::new(runs, &root).unwrap();
+		let mut merged = Vec::<PathMap>::new();
+		loop {
+			let chunk = merger.next_chunk(3).unwrap();
+			if chunk.is_empty() {
+				break;
+			}
+			assert!(chunk.len() <= 3);
+			merged.extend(chunk);
+		}
+		assert_eq!(merged.len(), expected.len());
+		for (a,b) in merged.iter().zip(expected.iter()) {
+			assert!(a.level.cmp_data == b.level.cmp_data);
+			assert_eq!(a.path.to_path(), b.path.to_path());
+		}
+
+		std::mem::drop(merger);
+		let dir = parent.join(format!("boxbopper-spill-{}", std::process::id()));
+		assert!(dir.exists());
+		std::mem::drop(spill);
+		assert!(!dir.exists());
+		let _ = std::fs::remove_dir(&parent);
+	}
+
+	#[test]
+	fn truncated_run_is_an_error() {
+		let level = Level::from_str("#########\n#   O   #\n# *&* O #\n#   *   #\n#  O    #\n#########\n").unwrap();
+		let (_, root, maps) = maps_two_pushes_deep(&level);
+		let parent = std::env::temp_dir().join("boxbopper-spill-test-truncated");
+		let mut spill = SpillDir::new(parent.to_str().unwrap()).unwrap();
+		let run = spill.write_run(&sort_dedup(maps)).unwrap();
+		let len = std::fs::metadata(&run).unwrap().len();
+		std::fs::OpenOptions::new().write(true).open(&run).unwrap().set_len(len - 3).unwrap();
+
+		let mut frontier = Frontier::Disk(RunMerger::new(vec![run], &root).unwrap());
+		let err = frontier.next_chunk(usize::MAX).err().unwrap();
+		assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+		std::mem::drop(frontier);
+		std::mem::drop(spill);
+		let _ = std::fs::remove_dir(&parent);
+	}
+}
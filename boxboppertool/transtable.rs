@@ -0,0 +1,178 @@
+// boxboppertool Copyright 2020-2021 David Atkinson
+//
+// transtable.rs: a table of the maps we've already seen, and the cheapest path we found to each one
+//
+// It is an open addressing hash table (linear probing), split into shards that each have their own lock, so all
+// the threads can use it at once. Shards start small and double in size as they fill up, until the memory budget
+// is used up. After that, maps that aren't already in the table aren't added (the search still works, but it may
+// look at the same map more than once).
+//
+// The cost is the number of moves, or (pushes << 16 | moves) when counting pushes.
+
+use boxbopperbase::level::{Level,CmpData};
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool,Ordering};
+
+const FIRST_SLOTS: usize = 256;		// slots per shard to begin with
+
+struct Shard {
+	slots: Vec::<Option<(CmpData,u32)>>,
+	used: usize,
+}
+
+pub struct TransTable {
+	shards: Vec::<Mutex<Shard>>,
+	max_slots: usize,			// per shard
+	slot_bytes: usize,			// including any bits stored on the heap
+	full: AtomicBool,
+}
+
+fn hash_key(key: &CmpData) -> u64 {
+	let mut h = ((key.human_x as u16 as u64) << 16) | (key.human_y as u16 as u64);
+	for b in key.blocks.iter() {
+		h = (h ^ b).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+		h ^= h >> 29;
+	}
+	// splitmix64 finish, so both the high bits (shard) and low bits (slot) are well mixed
+	h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+	h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+	h ^ (h >> 31)
+}
+
+impl Shard {
+	fn find(&self, key: &CmpData, hash: u64) -> (usize, bool) {
+		// where the key is (true), or the empty slot where it would go (false)
+		let cap = self.slots.len();
+		let mut idx = (hash as usize) % cap;
+		loop {
+			match &self.slots[idx] {
+				Some((k,_)) if k == key => return (idx, true),
+				Some(_) => idx = (idx + 1) % cap,
+				None => return (idx, false),
+			}
+		}
+	}
+	fn grow(&mut self, new_cap: usize) {
+		let old = std::mem::replace(&mut self.slots, vec![None; new_cap]);
+		for (key, len) in old.into_iter().flatten() {
+			let (idx, _) = self.find(&key, hash_key(&key));
+			self.slots[idx] = Some((key, len));
+		}
+	}
+}
+
+impl TransTable {
+	pub fn new(mem_mb: usize, num_threads: usize, base_level: &Level) -> TransTable {
+		let num_shards = (num_threads * 16).next_power_of_two().max(16);
+		let num_bits = base_level.w as usize * base_level.h as usize;
+		let heap_bytes = if num_bits > 256 { num_bits.div_ceil(64) * 8 } else { 0 };
+		let slot_bytes = std::mem::size_of::<Option<(CmpData,u32)>>() + heap_bytes;
+		let max_slots = (mem_mb * 1024 * 1024 / slot_bytes / num_shards).max(FIRST_SLOTS);
+		let shards = (0..num_shards).map(|_| Mutex::new(Shard { slots: vec![None; FIRST_SLOTS], used: 0 })).collect();
+		TransTable { shards, max_slots, slot_bytes, full: AtomicBool::new(false) }
+	}
+	fn shard(&self, hash: u64) -> &Mutex<Shard> {
+		&self.shards[(hash >> 48) as usize % self.shards.len()]
+	}
+	pub fn get(&self, key: &CmpData) -> Option<u32> {
+		let hash = hash_key(key);
+		let shard = self.shard(hash).lock().unwrap();
+		match shard.find(key, hash) {
+			(idx, true) => shard.slots[idx].as_ref().map(|(_,cost)| *cost),
+			_ => None,
+		}
+	}
+	pub fn insert_min(&self, key: &CmpData, cost: u32) -> Option<u32> {
+		// store cost if the map is new, or we've found a cheaper path to it
+		// returns what was stored before (None if the map is new)
+		let hash = hash_key(key);
+		let mut shard = self.shard(hash).lock().unwrap();
+		let (idx, found) = shard.find(key, hash);
+		if found {
+			let slot = shard.slots[idx].as_mut().unwrap();
+			let prev = slot.1;
+			if cost < prev {
+				slot.1 = cost;
+			}
+			return Some(prev);
+		}
+
+		// keep the shard no more than 3/4 full, so probes stay short
+		let cap = shard.slots.len();
+		if (shard.used + 1) * 4 > cap * 3 {
+			if cap >= self.max_slots {
+				self.full.store(true, Ordering::Relaxed);
+				return None;
+			}
+			shard.grow((cap * 2).min(self.max_slots));
+			let (idx, _) = shard.find(key, hash);
+			shard.slots[idx] = Some((key.clone(), cost));
+		} else {
+			shard.slots[idx] = Some((key.clone(), cost));
+		}
+		shard.used += 1;
+		None
+	}
+	pub fn len(&self) -> usize {
+		self.shards.iter().map(|s| s.lock().unwrap().used).sum()
+	}
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+	pub fn is_full(&self) -> bool {
+		// have we had to turn away a new map?
+		self.full.load(Ordering::Relaxed)
+	}
+	pub fn mem_bytes(&self) -> usize {
+		self.shards.iter().map(|s| s.lock().unwrap().slots.len()).sum::<usize>() * self.slot_bytes
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn key(n: usize) -> CmpData {
+		let mut key = CmpData::new();
+		key.human_x = (n % 7) as i16;
+		key.blocks[0] = n as u64;
+		key
+	}
+
+	#[test]
+	fn insert_min_keeps_the_cheapest() {
+		let level = Level::from_str("#####\n#&*O#\n#####\n").unwrap();
+		let table = TransTable::new(16, 2, &level);
+		assert_eq!(table.insert_min(&key(1), 10), None);
+		assert_eq!(table.insert_min(&key(1), 12), Some(10));		// dearer, so not stored
+		assert_eq!(table.get(&key(1)), Some(10));
+		assert_eq!(table.insert_min(&key(1), 7), Some(10));			// cheaper, so stored
+		assert_eq!(table.get(&key(1)), Some(7));
+		assert_eq!(table.get(&key(2)), None);
+		assert_eq!(table.len(), 1);
+
+		// the shards grow, and keep everything in them
+		(2..5000).for_each(|n| { table.insert_min(&key(n), n as u32); });
+		assert_eq!(table.len(), 4999);
+		assert!((2..5000).all(|n| table.get(&key(n)) == Some(n as u32)));
+		assert!(!table.is_full());
+	}
+
+	#[test]
+	fn full_table_turns_away_new_maps() {
+		// no memory to speak of, so each shard stays at its first size
+		let level = Level::from_str("#####\n#&*O#\n#####\n").unwrap();
+		let table = TransTable::new(0, 1, &level);
+		(0..10000).for_each(|n| { table.insert_min(&key(n), 5); });
+		assert!(table.is_full());
+		let kept: Vec::<usize> = (0..10000).filter(|n| table.get(&key(*n)).is_some()).collect();
+		assert_eq!(kept.len(), table.len());
+		assert!(kept.len() < 10000);
+
+		// maps already in the table can still get cheaper
+		let n = kept[0];
+		assert_eq!(table.insert_min(&key(n), 2), Some(5));
+		assert_eq!(table.get(&key(n)), Some(2));
+	}
+}
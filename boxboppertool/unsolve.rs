@@ -3,11 +3,10 @@
 // unsolve.rs: unsolve (create) a sokoban-style level
 
 use boxbopperbase::{moves_to_string};
-use boxbopperbase::level::{Level,CmpData};
+use boxbopperbase::level::{Level};
 use boxbopperbase::vector::Move;
 
 use std::rc::Rc;
-use std::collections::BTreeMap;
 
 use rayon::iter::IntoParallelRefMutIterator;
 use rayon::prelude::*;
@@ -15,6 +14,7 @@ use rayon::prelude::*;
 use crate::solve::{task_splitter,task_splitter_sort};	// task_splitter_mut
 use crate::pathnodemap::{PathMap};
 use crate::shrunkpath::{TreeNodeRef};	// PathTrait
+use crate::transtable::{TransTable};
 
 extern crate rand;
 extern crate rand_chacha;
@@ -48,7 +48,7 @@ pub fn select_unique_n_from(count: usize, len: usize, rng: &mut rand_chacha::Cha
 }
 
 
-pub fn unsolve_level(base_level_in: &Level, max_depth: u16, max_maps: usize, rng: &mut rand_chacha::ChaCha8Rng, verbosity: u32, num_threads: usize) -> Vec::<Level> {
+pub fn unsolve_level(base_level_in: &Level, max_depth: u16, max_maps: usize, table_mem: usize, rng: &mut rand_chacha::ChaCha8Rng, verbosity: u32, num_threads: usize) -> Vec::<Level> {
 	let base_level1 = base_level_in.clear_human_cloned();
 	let base_map = PathMap::new_from_level(&base_level1);
 	let _path_root = base_map.path.clone(); 	// we have to keep reference so the root doesn't dissappear
@@ -70,8 +70,9 @@ pub fn unsolve_level(base_level_in: &Level, max_depth: u16, max_maps: usize, rng
 	let mut maps2 = Vec::<PathMap>::new();
 	maps1.iter().for_each(|m| m.complete_solve_2(&base_level, &mut maps2));
 	let mut mapsr: Vec<PathMap> = maps2.iter().filter(|m| m.level.have_win_condition(&base_level) ).cloned().collect();
+	let unsolve_root = TreeNodeRef::new_root();		// again, keep a reference so the new root doesn't dissappear
 	mapsr.iter_mut().for_each(|map| { 			// reset the move count
-		map.path = TreeNodeRef::new_root();			// .clear(); 
+		map.path = unsolve_root.clone();			// .clear(); 
 	});
 	if verbosity > 1 { 
 		println!("final maps found: {}", mapsr.len()); 
@@ -81,7 +82,7 @@ pub fn unsolve_level(base_level_in: &Level, max_depth: u16, max_maps: usize, rng
 	}
 	let mut mapsr = Rc::new(mapsr);
 
-	let mut non_contenders = BTreeMap::<CmpData,u16>::new();
+	let non_contenders = TransTable::new(table_mem, num_threads, &base_level);
 	let mut contenders = Vec::<PathMap>::new();	
 	let mut contenders_2 = Vec::<PathMap>::new();
 	let mut max_max_counter = 0;
@@ -103,16 +104,10 @@ pub fn unsolve_level(base_level_in: &Level, max_depth: u16, max_maps: usize, rng
 
 		// shuffle mapsr->contenders->contenders_2->non_contenders
 		if verbosity > 1 { println!("keep top contenders..."); }
-		if non_contenders.len() < max_maps * 4 {
-			contenders_2.iter().for_each(|m| { 
-				let pre = non_contenders.get(&m.level.cmp_data);
-				if (pre.is_some() && *pre.unwrap() > m.path.len()) || pre.is_none() {
-					non_contenders.insert(m.level.cmp_data, m.path.len()); 
-				}
-			});
-		} else {
-			if verbosity > 0 { println!("--- Hit maximum old maps, not adding any more ---"); }
-		} 
+		contenders_2.par_iter().for_each(|m| { non_contenders.insert_min(&m.level.cmp_data, m.path.len() as u32); });
+		if non_contenders.is_full() && verbosity > 0 {
+			println!("--- Hit maximum old maps (table_mem), not adding any more ---");
+		}
 		contenders_2 = contenders;
 		contenders = mapsr.to_vec();
 		
@@ -159,9 +154,8 @@ pub fn unsolve_level(base_level_in: &Level, max_depth: u16, max_maps: usize, rng
 		// Remove from maps anything that is in non_contenders AND we already found a shorter path
 		if verbosity > 1 { println!("deduping using n-c: before {:>7}", maps.len()); }
 		maps.par_iter_mut().for_each(|m| {
-			let v = non_contenders.get(&m.level.cmp_data);
-			if v.is_some() {
-				if *v.unwrap() <= m.path.len() {
+			if let Some(len) = non_contenders.get(&m.level.cmp_data) {
+				if len <= m.path.len() as u32 {
 					m.flag = true;
 				}
 			}
@@ -9,12 +9,14 @@ use std::io::{BufRead, Write};
 
 use std::time::Duration;
 
-use crossterm::{terminal, event};
-use crossterm::event::{Event, KeyCode};
+use crossterm::{terminal, event, execute};
+use crossterm::event::{Event, KeyCode, MouseEventKind, MouseButton, EnableMouseCapture, DisableMouseCapture};
 
 use boxbopperbase::{Game};
-use boxbopperbase::vector::{Move};
-use boxbopperbase::level::{Level};
+use boxbopperbase::event::{GameEvent};
+use boxbopperbase::vector::{Move,Vector};
+use boxbopperbase::level::{Level,LevelParseError};
+use boxbopperbase::collection::{LevelCollection};
 use boxbopperbase::builtins::BUILTIN_LEVELS;
 
 use tui::Terminal;
@@ -28,9 +30,96 @@ use tui::text::{Span, Spans};
 // ✅❎❌⏹⬛⬜
 // ♒♊🔘🔲🔳🔴🔵📀💿🟠🟡🟢🟣🟤🟥🟦🟧🟨🟩🟪🟫🧿🧍👷🙂🙃😀😃😄🤔🗿
 // We use str here (instead of char) to allow for multi-width and multi-code
-// Wall, Space, Boxx, Hole, Human, HumanInHole, BoxxInHole
-const TEXT_OBJS: [[&str; 7]; 2] = [ ["#", " ", "*", "O", "&", "%", "@" ],
-									["░░", "  ", "❎", "🔳", "😀", "🤔", "✅"] ];
+// Wall, Space, Boxx, Hole, Human, HumanInHole, BoxxInHole, DeadSquare (hint), StuckBoxx
+const TEXT_OBJS: [[&str; 9]; 2] = [ ["#", " ", "*", "O", "&", "%", "@", ".", "*" ],
+									["░░", "  ", "❎", "🔳", "😀", "🤔", "✅", "··", "❌"] ];
+
+// Where the levels come from: the builtin levels, or a level file (which may have many levels)
+pub enum LevelSet {
+	Builtin,
+	Collection(LevelCollection),
+}
+
+impl LevelSet {
+	pub fn len(&self) -> u32 {
+		match self {
+			LevelSet::Builtin => BUILTIN_LEVELS.len() as u32,
+			LevelSet::Collection(c) => c.len() as u32,
+		}
+	}
+	pub fn new_game(&self, level_number: u32) -> Result<Game, LevelParseError> {
+		// no animation in the terminal, so moves happen straight away
+		// like Game::new, a level number that's too high gives the last level
+		let level_number = level_number.min(self.len().saturating_sub(1));
+		let level = match self {
+			LevelSet::Collection(c) => match c.get(level_number as usize) {
+				Some(level) => level.clone(),
+				None => Level::from_builtin(0),		// empty collection
+			},
+			LevelSet::Builtin => Level::from_builtin(level_number as usize),
+		};
+		level.map(|l| Game::new_headless(&l, level_number))
+	}
+	pub fn go_to(&self, level_number: u32, game: &mut Game, current_level: &mut u32, ui: &mut UiState) {
+		// a level in a file that we can't read just gets a message, so we can move on past it
+		*current_level = level_number;
+		match self.new_game(level_number) {
+			Ok(g) => { *game = g; },
+			Err(e) => { ui.status = format!("Unable to open level {}: {}", level_number, e); },
+		}
+	}
+}
+
+// A single save slot on disk, plus a message about the last save / load for the user
+pub struct SaveSlot {
+	pub filename: String,
+	pub message: String,
+}
+
+impl SaveSlot {
+	pub fn save(&mut self, game: &Game) {
+		self.message = match std::fs::write(&self.filename, game.save_state()) {
+			Ok(_) => format!("Saved game to {}", self.filename),
+			Err(e) => format!("Unable to save game to {}: {}", self.filename, e),
+		};
+	}
+	pub fn load(&mut self, game: &mut Game, current_level: &mut u32) {
+		let state = match std::fs::read_to_string(&self.filename) {
+			Ok(s) => s,
+			Err(e) => {
+				self.message = format!("Unable to load game from {}: {}", self.filename, e);
+				return;
+			},
+		};
+		match Game::load_state(&state) {
+			Ok(g) => {
+				*game = g;
+				game.set_headless();
+				*current_level = game.level_number;
+				self.message = format!("Loaded game from {}", self.filename);
+			},
+			Err(e) => { self.message = format!("Unable to load game from {}: {}", self.filename, e); },
+		}
+	}
+}
+
+// Things the terminal ui keeps track of between screen updates
+pub struct UiState {
+	pub mouse_down_pt: Option<Vector>,		// where a mouse drag started
+	pub show_hints: bool,					// show squares a boxx can't be pushed out of
+	pub status: String,						// the latest thing that happened in the game worth mentioning
+}
+
+impl UiState {
+	pub fn update_status(&mut self, game: &mut Game) {
+		for ev in game.drain_events() {
+			match ev {
+				GameEvent::HumanMoved { .. } | GameEvent::BoxPushed { .. } => {},	// too many of these to mention
+				_ => { self.status = ev.to_string(); },
+			}
+		}
+	}
+}
 
 pub fn basic_ui_get_user_input() -> String {
 	let mut line = String::new();
@@ -43,11 +132,15 @@ pub fn basic_ui_get_user_input() -> String {
 
 pub fn basic_ui_display_game(game: &Game, use_emoji: bool) {
 	println!("------------------------------------------------------------------------------");
-	println!("{} moves: {}", game.get_num_moves(), game.get_moves_string());
+	println!("{} moves, {} pushes: {}", game.get_num_moves(), game.get_num_pushes(), game.get_moves_string_lurd());
 	println!("------------------------------------------------------------------------------");
 	println!();
 	println!("{}", get_level_string(game, use_emoji));
 	println!();
+	if game.is_deadlocked() {
+		println!("Stuck! Some boxes can never reach a hole. Undo (z) or reset (`).");
+		println!();
+	}
 }
 
 fn get_level_string(game: &Game, use_emoji: bool) -> String {
@@ -92,6 +185,8 @@ fn level_str_to_vecs(base_str: &String, use_emoji: bool) -> Vec<Spans> {
 			'&' => line.push(Span::styled(TEXT_OBJS[ue][4], Style::default().fg(Color::LightYellow))),
 			'%' => line.push(Span::styled(TEXT_OBJS[ue][5], Style::default().fg(Color::LightYellow))),
 			'@' => line.push(Span::styled(TEXT_OBJS[ue][6], Style::default().fg(Color::LightGreen))),
+			'.' => line.push(Span::styled(TEXT_OBJS[ue][7], Style::default().fg(Color::DarkGray))),
+			'!' => line.push(Span::styled(TEXT_OBJS[ue][8], Style::default().fg(Color::LightRed).add_modifier(Modifier::REVERSED))),
 			//'\n' | '\r' => { vecs.push(Spans::from(line.clone())); line.clear(); },
 			_   => { vecs.push(Spans::from(line.clone())); line.clear(); },
 		};
@@ -100,8 +195,37 @@ fn level_str_to_vecs(base_str: &String, use_emoji: bool) -> Vec<Spans> {
 	return vecs.iter().map(|v| { Spans::from(v.clone()) } ).collect();
 }
 
+fn get_marked_level_string(game: &Game, show_hints: bool) -> String {
+	// mark the boxxes that are stuck, and (if asked) the empty squares that a boxx could never be pushed out of
+	let line_len = game.get_level_width() as usize + 3;		// outer walls and newline
+	let mut chars: Vec<char> = game.get_level_string().chars().collect();
+	if show_hints {
+		for pt in game.get_dead_squares() {
+			let idx = (pt.1 as usize + 1) * line_len + pt.0 as usize + 1;
+			if chars[idx] == ' ' {
+				chars[idx] = '.';
+			}
+		}
+	}
+	for pt in game.get_stuck_boxes() {
+		chars[(pt.1 as usize + 1) * line_len + pt.0 as usize + 1] = '!';
+	}
+	chars.into_iter().collect()
+}
+
+// Work out which level square is under a terminal cell, using the same layout as tui_inner
+fn screen_to_level_pt(state: &Game, screen_width: u16, column: u16, row: u16, use_emoji: bool) -> Vector {
+	let cell_width = if use_emoji { 2 } else { 1 };
+	let line_width = (state.get_level_width() as i32 + 2) * cell_width;
+	let left = (screen_width as i32 / 2 - line_width / 2).max(0);	// the level is centered, the same way tui does it
+	let top = 3;											// below the top widget
+	let x = (column as i32 - left).div_euclid(cell_width) - 1;	// take off the outer wall
+	let y = row as i32 - top - 1;
+	Vector(x, y)
+}
+
 // OK will return bool (true=keep going), Err will return string
-fn tui_inner(state: &mut Game, current_level: &mut u32, use_emoji: bool) -> Result <bool, String> {
+fn tui_inner(state: &mut Game, current_level: &mut u32, levels: &LevelSet, slot: &mut SaveSlot, ui: &mut UiState, use_emoji: bool) -> Result <bool, String> {
 	let stdout = io::stdout();
 	let backend = CrosstermBackend::new(stdout);
 	let mut terminal = match Terminal::new(backend) {
@@ -129,11 +253,12 @@ fn tui_inner(state: &mut Game, current_level: &mut u32, use_emoji: bool) -> Resu
 		// Format various top strings
 		let current_level_str = format!("{:2}", current_level);
 		let num_moves_str = format!("{:3}", state.get_num_moves());
+		let num_pushes_str = format!("{:3}", state.get_num_pushes());
 
-		// Format the moves list string: 
+		// Format the moves list string (pushes are in upper case): 
 		// If the moves list is longer than the width allocated, show only the most recent moves
-		let moves_chars_avail = size.width as usize - 2 - 9 - 11 - 1;
-		let ms = state.get_moves_string();
+		let moves_chars_avail = (size.width as usize).saturating_sub(2 + 9 + 11 + 12 + 1);
+		let ms = state.get_moves_string_lurd();
 		let (_, moves_str) = if ms.len() > moves_chars_avail {
 			ms.split_at(ms.len() - moves_chars_avail)
 		} else {
@@ -146,6 +271,8 @@ fn tui_inner(state: &mut Game, current_level: &mut u32, use_emoji: bool) -> Resu
 				Span::styled(current_level_str, Style::default().fg(Color::LightMagenta)),
 				Span::raw(" Moves: "),
 				Span::styled(num_moves_str, Style::default().fg(Color::LightMagenta)),
+				Span::raw(" Pushes: "),
+				Span::styled(num_pushes_str, Style::default().fg(Color::LightMagenta)),
 				Span::raw(" "),
 				Span::styled(moves_str, Style::default().fg(Color::Blue)),
 			];
@@ -175,6 +302,16 @@ fn tui_inner(state: &mut Game, current_level: &mut u32, use_emoji: bool) -> Resu
 			Span::raw("ext level   "),
 			Span::styled("P", Style::default().add_modifier(Modifier::UNDERLINED)),
 			Span::raw("revious level   "),
+			Span::styled("Z", Style::default().add_modifier(Modifier::UNDERLINED)),
+			Span::raw(" undo   "),
+			Span::styled("Y", Style::default().add_modifier(Modifier::UNDERLINED)),
+			Span::raw(" redo   "),
+			Span::styled("S", Style::default().add_modifier(Modifier::UNDERLINED)),
+			Span::raw("ave   "),
+			Span::styled("O", Style::default().add_modifier(Modifier::UNDERLINED)),
+			Span::raw("pen save   "),
+			Span::styled("H", Style::default().add_modifier(Modifier::UNDERLINED)),
+			Span::raw(if ui.show_hints { "ide hints   " } else { "ints   " }),
 		];
 
 		// Add the relevant movement commands
@@ -194,8 +331,14 @@ fn tui_inner(state: &mut Game, current_level: &mut u32, use_emoji: bool) -> Resu
 		}
 
 		// Menu widget title is based on if we have completed the level (or not)
-		let (menu_col, menu_title) = if state.have_win_condition() {
+		let (menu_col, menu_title) = if slot.message.len() > 0 {
+			( Color::Yellow, slot.message.as_str() )
+		} else if !ui.status.is_empty() {
+			( Color::LightGreen, ui.status.as_str() )
+		} else if state.have_win_condition() {
 			( Color::LightGreen, "Level has been completed!" )
+		} else if state.is_deadlocked() {
+			( Color::LightRed, "Stuck! Z to undo, or ` to reset" )
 		} else {
 			( Color::White, "Commands" )
 		};
@@ -216,7 +359,7 @@ fn tui_inner(state: &mut Game, current_level: &mut u32, use_emoji: bool) -> Resu
 		rect.render_widget(menu_widget, chunks[2]);
 
 		// Create the game widget
-		let base_str = state.get_level_string();
+		let base_str = get_marked_level_string(state, ui.show_hints);
 		let game_text_vecs = level_str_to_vecs(&base_str, use_emoji);
 		let game_widget = Paragraph::new(game_text_vecs) 
 			.alignment(Alignment::Center)
@@ -247,27 +390,48 @@ fn tui_inner(state: &mut Game, current_level: &mut u32, use_emoji: bool) -> Resu
 	}
 
 	// Process the event
+	if let Event::Key(_) = r.as_ref().unwrap() {
+		slot.message.clear();
+		ui.status.clear();
+	}
 	match r.unwrap() {
 		Event::Key(ev) => match ev.code {
 			KeyCode::Char('Q') | KeyCode::Char('q') | KeyCode::Esc => { return Ok(false); },
-			KeyCode::Char('`')                       => { *state = Game::new(*current_level); },
+			KeyCode::Char('`')                       => { levels.go_to(*current_level, state, current_level, ui); },
 			KeyCode::Char('N') | KeyCode::Char('n')  => { 
-				if *current_level < BUILTIN_LEVELS.len() as u32 - 1 {
-					*current_level += 1;
-					*state = Game::new(*current_level);
+				if *current_level < levels.len() - 1 {
+					levels.go_to(*current_level + 1, state, current_level, ui);
 				}},
 			KeyCode::Char('P') | KeyCode::Char('p')  => { 
 				if *current_level > 0 {
-					*current_level -= 1;
-					*state = Game::new(*current_level);
+					levels.go_to(*current_level - 1, state, current_level, ui);
 				}},
+			KeyCode::Char('Z') | KeyCode::Char('z') | KeyCode::Backspace => { state.undo(); },
+			KeyCode::Char('Y') | KeyCode::Char('y')  => { state.redo(); },
+			KeyCode::Char('S') | KeyCode::Char('s')  => { slot.save(state); },
+			KeyCode::Char('O') | KeyCode::Char('o')  => { slot.load(state, current_level); },
+			KeyCode::Char('H') | KeyCode::Char('h')  => { ui.show_hints = !ui.show_hints; },
 			KeyCode::Char('U') | KeyCode::Char('u') | KeyCode::Up    => state.append_move(&Move::Up),
 			KeyCode::Char('R') | KeyCode::Char('r') | KeyCode::Right => state.append_move(&Move::Right),
 			KeyCode::Char('D') | KeyCode::Char('d') | KeyCode::Down  => state.append_move(&Move::Down),
 			KeyCode::Char('L') | KeyCode::Char('l') | KeyCode::Left  => state.append_move(&Move::Left),
 			_ => {}
 		},
-		Event::Mouse(_event) => {},
+		Event::Mouse(ev) => {
+			// click to walk, or drag a boxx to where it should be pushed
+			let width = terminal.size().map(|r| r.width).unwrap_or(0);
+			let pt = screen_to_level_pt(state, width, ev.column, ev.row, use_emoji);
+			match ev.kind {
+				MouseEventKind::Down(MouseButton::Left) => { ui.mouse_down_pt = Some(pt); },
+				MouseEventKind::Up(MouseButton::Left) => {
+					match ui.mouse_down_pt.take() {
+						Some(from) if from != pt => { state.push_box_to(from, pt); },
+						_ => { state.walk_to(pt); },
+					}
+				},
+				_ => {},
+			}
+		},
 		Event::Resize(_width, _height) => {},
 	}
 
@@ -275,11 +439,19 @@ fn tui_inner(state: &mut Game, current_level: &mut u32, use_emoji: bool) -> Resu
 }
 
 
-pub fn basic_ui_inner(state: &mut Game, current_level: &mut u32, use_emoji: bool) -> Result<bool, String> {
+pub fn basic_ui_inner(state: &mut Game, current_level: &mut u32, levels: &LevelSet, slot: &mut SaveSlot, ui: &mut UiState, use_emoji: bool) -> Result<bool, String> {
 	println!("\n\n");
 	println!("==============================================================================");			
 	println!("Level {}", *current_level);
 	basic_ui_display_game(state, use_emoji);
+	if slot.message.len() > 0 {
+		println!("{}", slot.message);
+		slot.message.clear();
+	}
+	if !ui.status.is_empty() {
+		println!("{}", ui.status);
+		ui.status.clear();
+	}
 	
 	if state.have_win_condition() {
 		println!(r"    \  /\  / | |\ |");
@@ -288,9 +460,9 @@ pub fn basic_ui_inner(state: &mut Game, current_level: &mut u32, use_emoji: bool
 	}
 	
 	if !state.have_win_condition() {
-		print!("Commands (Quit `reset Next Prev Up Down Left Right) > ");
+		print!("Commands (Quit `reset Next Prev Z=undo Y=redo Save Open Up Down Left Right) > ");
 	} else {
-		print!("Level has beel completed! (Quit `reset Next Prev) > ");
+		print!("Level has beel completed! (Quit `reset Next Prev Z=undo Save Open) > ");
 	}
 
 	let r = io::stdout().flush();
@@ -303,15 +475,17 @@ pub fn basic_ui_inner(state: &mut Game, current_level: &mut u32, use_emoji: bool
 	// this function blocks, waiting for user input (it is meant to)
 	basic_ui_get_user_input().chars().for_each( |c| match c {
 		'q' | 'Q' => quit = true,
-		'`' => *state = Game::new(*current_level),
-		'n' | 'N' =>  { if *current_level < BUILTIN_LEVELS.len() as u32 {
-					*current_level += 1;
-					*state = Game::new(*current_level);
+		'`' => levels.go_to(*current_level, state, current_level, ui),
+		'n' | 'N' =>  { if *current_level < levels.len() - 1 {
+					levels.go_to(*current_level + 1, state, current_level, ui);
 				}},
 		'p' | 'P' => { if *current_level > 0 {
-					*current_level -= 1;
-					*state = Game::new(*current_level);
+					levels.go_to(*current_level - 1, state, current_level, ui);
 				}},
+		'z' | 'Z' => { state.undo(); },
+		'y' | 'Y' => { state.redo(); },
+		's' | 'S' => { slot.save(state); },
+		'o' | 'O' => { slot.load(state, current_level); },
 		'u' | 'U' => state.append_move(&Move::Up),
 		'r' | 'R' => state.append_move(&Move::Right),
 		'd' | 'D' => state.append_move(&Move::Down),
@@ -325,6 +499,8 @@ fn main() -> Result<(), String> {
 	let args: Vec::<String> = std::env::args().collect();
 	let mut filename: String = String::from("");
 	let mut builtin: u32 = 0;
+	let mut index: u32 = 0;
+	let mut save_file: String = String::from("boxbopper.sav");
 	let mut use_emoji: bool = false;
 	let mut basic_ui: bool = false;
 	let mut quit = false;
@@ -345,6 +521,8 @@ fn main() -> Result<(), String> {
 			match left {
 				"filename"  => { filename = String::from(right); },
 				"builtin"   => { builtin = right.parse::<u32>().unwrap(); }
+				"index"     => { index = right.parse::<u32>().unwrap(); }
+				"save_file" => { save_file = String::from(right); },
 				"use_emoji"   => { use_emoji = right.parse::<bool>().unwrap(); },
 				"basic_ui"   => { basic_ui = right.parse::<bool>().unwrap(); },
 				_ => {
@@ -360,46 +538,70 @@ fn main() -> Result<(), String> {
 	}
 
 	if show_help {
-		println!("filename=FILENAME      load level from FILENAME");
+		println!("filename=FILENAME      load level(s) from FILENAME");
+		println!("index=NUM              start with level NUM in FILENAME   0+");
 		println!("builtin=NUM            start with builtin level NUM       0-78");
+		println!("save_file=FILENAME     save and open games using FILENAME default: boxbopper.sav");
 		println!("use_emoji=true         use emoji for display              true / false");
 		println!("basic_ui=true          use a basic ui only                true / false");
 	}
 	
-	let mut state = if filename.len() > 0 {
-		Game::new_from_level(&Level::from_file(&filename).expect("Unable to open specified file"), 0)
+	let (levels, mut current_level) = if filename.len() > 0 {
+		match LevelCollection::from_file(&filename) {
+			Ok(c) if c.is_empty() => {
+				println!("No levels found in specified file.");
+				return Ok(());
+			},
+			Ok(c) => {
+				let last = c.len() as u32 - 1;
+				(LevelSet::Collection(c), index.min(last))
+			},
+			Err(e) => {
+				println!("Unable to open specified file: {}", e);
+				return Ok(());
+			},
+		}
 	} else {
-		Game::new(builtin)
+		(LevelSet::Builtin, builtin)
 	};
-		
-	let mut current_level: u32 = builtin;
-	
+
 	if quit || show_help {
 		return Ok(());
 	}
 
+	let mut state = match levels.new_game(current_level) {
+		Ok(g) => g,
+		Err(e) => {
+			println!("Unable to open level {}: {}", current_level, e);
+			return Ok(());
+		},
+	};
+	current_level = state.level_number;
+	let mut slot = SaveSlot { filename: save_file, message: String::new() };
+	let mut ui = UiState { mouse_down_pt: None, show_hints: false, status: String::new() };
+
 	if !basic_ui {
 		let backend = CrosstermBackend::new(io::stdout());
 		let mut terminal = Terminal::new(backend).expect("Failed to open terminal.");
 		terminal::enable_raw_mode().expect("Failed to enable terminal raw mode.");
+		let _ = execute!(io::stdout(), EnableMouseCapture);	// clicking to walk is optional
 		let _ = terminal.clear(); // don't care if this fails at this stage
 	}
 
 	let mut error_string = "".to_string();
 
 	while !quit {
-		// process move queue
-		while state.is_queued_moves() {
-			state.process_moves();
-		}
+		// process move queue (all at once, as the game is headless)
+		state.process_moves();
+		ui.update_status(&mut state);
 
 		// run display/input function
 		let r = if basic_ui {
 			// run basic ui
-			basic_ui_inner(&mut state, &mut current_level, use_emoji)
+			basic_ui_inner(&mut state, &mut current_level, &levels, &mut slot, &mut ui, use_emoji)
 		} else {
 			// run tui
-			tui_inner(&mut state, &mut current_level, use_emoji)
+			tui_inner(&mut state, &mut current_level, &levels, &mut slot, &mut ui, use_emoji)
 		};
 
 		// are we quitting?
@@ -418,6 +620,7 @@ fn main() -> Result<(), String> {
 	if !basic_ui {
 		let backend = CrosstermBackend::new(io::stdout());
 		let mut terminal = Terminal::new(backend).expect("Failed to open terminal.");
+		let _ = execute!(io::stdout(), DisableMouseCapture);
 		let _ = terminal::disable_raw_mode(); 		// we don't care if any of these return errors, as we are quitting
 		let _ = terminal.clear();
 		let _ = terminal.show_cursor();
@@ -8,6 +8,12 @@ export class Controller {
 				document.gameManager.nextLevel();
 			} else if(ev.key == 'P' || ev.key == 'p') {
 				document.gameManager.prevLevel();
+			} else if(ev.key == 'Z' || ev.key == 'z' || ev.key == 'Backspace') {
+				document.gameManager.game.undo_js();
+			} else if(ev.key == 'Y' || ev.key == 'y') {
+				document.gameManager.game.redo_js();
+			} else if(ev.key == 'H' || ev.key == 'h') {
+				document.gameManager.showHints = !document.gameManager.showHints;
 			} else if((ev.key == ' ' || ev.key == 'Enter') && document.gameManager.game.have_win_condition()) {
 				document.gameManager.nextLevel();
 			} else if(ev.key == 'ArrowUp' | ev.code == 'KeyW') {		
@@ -1,4 +1,4 @@
-import { Game, Vector, Level, Move, Obj, load_builtin } from 'wasm-game';
+import { Game, Vector, Level, Move, Obj, EventKind, load_builtin } from 'wasm-game';
 import CONFIG from './config';
 import { View } from './view';
 import { Controller } from './controller';
@@ -7,6 +7,7 @@ import Storage from './storage';
 export class GameManager {
 	constructor() {
 		this.levelNumber = 0;
+		this.showHints = false;			// shade the squares a box can't be pushed out of
 		this.transitionList = [];
 		this.restart(this.levelNumber);
 		document.getElementById('prev_button').disabled = (this.levelNumber==0);
@@ -20,47 +21,50 @@ export class GameManager {
 			document.gameManager.nextLevel();
 		});
 		document.getElementById("retry_button").addEventListener('click', function() {
+			Storage.clearSavedGame(document.gameManager.levelTitle);
 			document.gameManager.restart(document.gameManager.levelNumber);
 		});
 		
-		this.view.container.addEventListener('click', function(ev) {
+		// click (or tap) a square to walk there, or drag a box to where you want it pushed
+		this.view.container.addEventListener('pointerdown', function(ev) {
+			var gm = document.gameManager;
+			gm.pointerDownAt = [ Math.floor(ev.offsetX / gm.view.unitOnScreen), Math.floor(ev.offsetY / gm.view.unitOnScreen) ];
+		});
+		this.view.container.addEventListener('pointerup', function(ev) {
 			var gm = document.gameManager;
 			var x = Math.floor(ev.offsetX / gm.view.unitOnScreen);
 			var y = Math.floor(ev.offsetY / gm.view.unitOnScreen);
-			// if in same y as human, apply L/R to try and make x match
-			var diff = 0;
-			var m;
-			if(gm.game.human_pos.as_array()[1] == y) {
-				diff = x - gm.game.human_pos.as_array()[0];
-				console.log("x: ", x, " y: ", y);
-
-				if(diff > 0) {
-					m = 1;
-				} else if(diff < 0) {
-					m = 3;
-					diff = Math.abs(diff);
-				}
-			} else if(gm.game.human_pos.as_array()[0] == x) {
-				diff = y - gm.game.human_pos.as_array()[1];
-				if(diff > 0) {
-					m = 2;
-				} else if(diff < 0) {
-					m = 0;
-					diff = Math.abs(diff);
-				}
-			}
-			if(diff <= 0 || diff > 50) return; // no clear direction or something screwy
-			for(var i=0;i<diff;i++) {
-				gm.game.append_move_js(m);
+			var from = gm.pointerDownAt;
+			gm.pointerDownAt = null;
+			if(from && (from[0] != x || from[1] != y)) {
+				gm.game.push_box_to_js(from[0], from[1], x, y);
+			} else {
+				gm.game.walk_to_js(x, y);
 			}
 		});
 		document.gameManager = this;			// need to persist the object in the document, our callbacks aren't getting called with correct this
 	}
 
 	restart(levelNum) {	
-		this.game = new Game(this.levelNumber);
+		var level = Level.from_builtin_js(this.levelNumber);
+		// turn wide levels sideways to fit portrait screens
+		if(window.innerHeight > window.innerWidth && level.get_level_width() > level.get_level_height()) {
+			level = level.rotate_cw();
+		}
+		this.game = Game.new_from_level(level, this.levelNumber);
 		this.levelTitle = this.game.get_level_title();
 		this.bestScore = Storage.getBestScore(this.levelTitle);
+		// pick up where we left off, if we were part way through this level
+		var saved = Storage.getSavedGame(this.levelTitle);
+		if(saved) {
+			try {
+				this.game = Game.load_state_js(saved);
+			} catch(e) {
+				console.log("Unable to resume saved game: ", e);
+				Storage.clearSavedGame(this.levelTitle);
+			}
+		}
+		this.savedMoves = this.game.get_moves_string();
 		if(this.view) {
 			this.view.setUp(this.game.get_level_width(),this.game.get_level_height());
 			document.getElementById('prev_button').disabled = (levelNum==0);
@@ -81,15 +85,25 @@ export class GameManager {
 
 	render() {
 		var gm = document.gameManager;
-		if(gm.game.have_win_condition()) {
-			if(isNaN(parseInt(Storage.getBestScore(gm.levelTitle))) || gm.game.num_moves < Storage.getBestScore(gm.levelTitle)) {
-				gm.bestScore = gm.game.num_moves;
-				Storage.setBestScore(gm.levelTitle, gm.bestScore);
-			}
-		}
 		if(gm.game) {
 			gm.game.process_moves_js();
+			gm.game.drain_events_js().forEach(function(ev) {
+				if(ev.kind == EventKind.LevelSolved && Storage.isBetterScore(Storage.getBestScore(gm.levelTitle), ev.moves, ev.pushes)) {
+					gm.bestScore = ev.moves + '/' + ev.pushes;
+					Storage.setBestScore(gm.levelTitle, gm.bestScore);
+				}
+			});
 			gm.view.render(gm.game, gm.game.human_pos);
+			// keep the saved game up to date, and forget it once the level is done
+			var moves = gm.game.get_moves_string();
+			if(moves != gm.savedMoves) {
+				if(gm.game.have_win_condition()) {
+					Storage.clearSavedGame(gm.levelTitle);
+				} else {
+					Storage.saveGame(gm.levelTitle, gm.game.save_state_js());
+				}
+				gm.savedMoves = moves;
+			}
 		}
 	}
 
@@ -1,10 +1,26 @@
 export default {
 	getBestScore: function(levelTitle) {
-		var x = parseInt(localStorage.getItem('bxbop_bestScore_'+levelTitle));
-		if(isNaN(x)) return 'unsolved';
+		// stored as "moves/pushes" (older scores are just moves)
+		var x = localStorage.getItem('bxbop_bestScore_'+levelTitle);
+		if(x === null || isNaN(parseInt(x))) return 'unsolved';
 		return x;
 	},
+	isBetterScore: function(bestScore, moves, pushes) {
+		var best = String(bestScore).split('/').map(function(n) { return parseInt(n); });
+		if(isNaN(best[0])) return true;
+		if(moves != best[0]) return moves < best[0];
+		return isNaN(best[1]) || pushes < best[1];
+	},
 	setBestScore: function(levelTitle, bestScore) {
 		localStorage.setItem('bxbop_bestScore_'+levelTitle, bestScore);
 	},
+	getSavedGame: function(levelTitle) {
+		return localStorage.getItem('bxbop_savedGame_'+levelTitle);
+	},
+	saveGame: function(levelTitle, state) {
+		localStorage.setItem('bxbop_savedGame_'+levelTitle, state);
+	},
+	clearSavedGame: function(levelTitle) {
+		localStorage.removeItem('bxbop_savedGame_'+levelTitle);
+	},
 }
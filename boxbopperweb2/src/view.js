@@ -120,6 +120,19 @@ export class View {
 			}, this);
 		}, this);
 		
+		// hints: shade the squares a box could never be pushed out of
+		if(document.gameManager.showHints && this.context) {
+			const deadSquares = game.get_dead_squares_js();
+			this.context.fillStyle = 'rgba(0,0,0,0.35)';
+			deadSquares.forEach( function(dead, i) {
+				if(dead) {
+					var x = i % game.get_level_width();
+					var y = Math.floor(i / game.get_level_width());
+					this.context.fillRect(this.scaleToScreen(x),this.scaleToScreen(y),this.unitOnScreen,this.unitOnScreen);
+				}
+			}, this);
+		}
+
 		// render human
 		var sprites = game.get_sprites_js();
 		sprites.forEach( function(spriteinfo) {
@@ -130,6 +143,14 @@ export class View {
 			}
 		});
 
+		// tint the boxes that can never get to a hole
+		if(game.is_deadlocked() && this.context) {
+			this.context.fillStyle = 'rgba(255,0,0,0.4)';
+			game.get_stuck_boxes_js().forEach( function(pt) {
+				this.context.fillRect(this.scaleToScreen(pt[0]),this.scaleToScreen(pt[1]),this.unitOnScreen,this.unitOnScreen);
+			}, this);
+		}
+
 		// render scoreboard
 		var gm = document.gameManager;
 		if(gm.game.have_win_condition()) {
@@ -143,6 +164,9 @@ export class View {
 			}); */
 			document.getElementById("moves_taken").innerHTML = mt;
 			document.getElementById("solved").innerHTML = "Solved!";
+		} else if(gm.game.is_deadlocked()) {
+			document.getElementById("moves_taken").innerHTML = "";
+			document.getElementById("solved").innerHTML = "Stuck! Press Z to undo, or Retry.";
 		} else {
 			document.getElementById("moves_taken").innerHTML = "";
 			document.getElementById("solved").innerHTML = "";
@@ -150,6 +174,6 @@ export class View {
 		document.getElementById("level_num").innerHTML = gm.levelNumber;
 		document.getElementById("level_title").innerHTML = gm.levelTitle;
 		document.getElementById("best_score").innerHTML = gm.bestScore;
-		document.getElementById("num_moves").innerHTML = gm.game.num_moves;
+		document.getElementById("num_moves").innerHTML = gm.game.num_moves + '/' + gm.game.num_pushes;
 	}
 }
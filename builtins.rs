@@ -28,7 +28,7 @@ title: basics-34
 num: 1
 depth: 19
 moves: 34
-path: RRRRRRLLLDRRRLLLDRRRLLLDRRR
+path: RRDDLDRRRRRLLLURRRLLLURRRLLLLURRRR
 time: 0.7
 ",
 
@@ -0,0 +1,165 @@
+// Box Bopper: Sokoban-like game
+// Copyright David Atkinson 2020-2021
+//
+// collection.rs: a collection (pack) of levels read from a single file
+//
+// Levels are separated by blank lines and/or Title: headers, e.g.
+//
+//   Title: first           ##########
+//   #######                #&   *  O#
+//   #@ $ .#       or       ##########
+//   #######
+//                          title: first
+//   Title: second          moves: 6
+//   ...                    
+//                          ##########
+//                          ...
+//
+// Lines starting with ';' are comments. Key/value lines found between two levels belong to the level
+// above, unless they come after a blank line (or the level above already has a title) and contain a title,
+// in which case they are the header of the level below.
+// Anything after a level's key/value lines that isn't a level itself (e.g. a diagram) is part of that level.
+// A block that can't be read in only gives an error when that level is asked for, unless none of the blocks can
+// be read in, in which case from_str() gives the first error.
+
+use crate::level::{Level,LevelParseError};
+
+#[derive(Clone)]
+pub struct LevelCollection {
+	levels: Vec::<Result<Level,LevelParseError>>,
+}
+
+fn is_grid_line(line: &str) -> bool {
+	// a row with a typo in it still counts, so that we can report where the typo is
+	let t = line.trim();
+	line.contains('#') && (line.chars().all(|c| "# *O&%@$.+-_".contains(c)) || (t.starts_with('#') && t.ends_with('#') && !t.contains(':')))
+}
+
+fn is_comment_line(line: &str) -> bool {
+	line.trim_start().starts_with(';')
+}
+
+fn has_title(lines: &[&str]) -> bool {
+	lines.iter().any(|l| {
+		match l.find(':') {
+			Some(idx) => l[0..idx].trim().eq_ignore_ascii_case("title"),
+			None => false,
+		}
+	})
+}
+
+struct LevelText<'a> {
+	first_line: usize,			// index of the first grid line in the file
+	grid: Vec::<&'a str>,
+	header: Vec::<&'a str>,
+	trailer: Vec::<&'a str>,
+}
+
+impl LevelText<'_> {
+	fn to_level(&self) -> Result<Level,LevelParseError> {
+		let mut level_str = self.grid.join("\n");
+		level_str += "\n\n";
+		for line in self.header.iter().chain(self.trailer.iter()) {
+			level_str += line;
+			level_str += "\n";
+		}
+		Level::from_str(&level_str).map_err(|e| e.with_line_offset(self.first_line))
+	}
+}
+
+impl LevelCollection {
+	pub fn from_str(s: &str) -> Result<LevelCollection, LevelParseError> {
+		// first split the file up into grids, and the lines between them
+		let mut texts = Vec::<LevelText>::new();
+		let mut between = Vec::<&str>::new();
+		let mut in_grid = false;
+		for (count, line) in s.lines().enumerate() {
+			if is_comment_line(line) {
+				continue;
+			}
+			if is_grid_line(line) {
+				if !in_grid {
+					let mut text = LevelText { first_line: count, grid: Vec::new(), header: Vec::new(), trailer: Vec::new() };
+					LevelCollection::split_between(texts.last_mut(), &mut text, &between);
+					between.clear();
+					texts.push(text);
+					in_grid = true;
+				}
+				texts.last_mut().unwrap().grid.push(line);
+			} else {
+				in_grid = false;
+				between.push(line);
+			}
+		}
+		if let Some(text) = texts.last_mut() {
+			text.trailer.extend_from_slice(&between);
+		}
+
+		// then read in each level
+		let mut collection = LevelCollection { levels: Vec::<Result<Level,LevelParseError>>::new() };
+		let mut kept = Vec::<LevelText>::new();
+		for text in texts.into_iter() {
+			let level = text.to_level();
+			if let (Err(_), Some(prev)) = (&level, kept.last_mut()) {
+				if text.header.is_empty() && prev.trailer.iter().any(|l| Level::is_keyval_line(l)) {
+					// not a level, and after the key/values of the level above, so it's part of that level
+					prev.trailer.extend_from_slice(&text.grid);
+					prev.trailer.extend_from_slice(&text.trailer);
+					*collection.levels.last_mut().unwrap() = prev.to_level();
+					continue;
+				}
+			}
+			collection.levels.push(level);
+			kept.push(text);
+		}
+		if let Some(Err(e)) = collection.levels.first() {
+			if collection.levels.iter().all(|l| l.is_err()) {
+				return Err(e.clone());
+			}
+		}
+		Ok(collection)
+	}
+	fn split_between<'a>(prev: Option<&mut LevelText<'a>>, next: &mut LevelText<'a>, between: &[&'a str]) {
+		// decide which of the lines between two grids belong to the previous level, and which to the next
+		let prev = match prev {
+			Some(p) => p,
+			None => {
+				next.header.extend_from_slice(between);
+				return;
+			},
+		};
+		let last_blank = between.iter().rposition(|l| l.trim().len() == 0);
+		let (before, after) = match last_blank {
+			Some(idx) => between.split_at(idx + 1),
+			None => between.split_at(0),
+		};
+		let prev_has_title = has_title(&prev.header) || has_title(before);
+		if has_title(after) && (last_blank.is_some() || prev_has_title) {
+			prev.trailer.extend_from_slice(before);
+			next.header.extend_from_slice(after);
+		} else {
+			prev.trailer.extend_from_slice(between);
+		}
+	}
+	pub fn from_file(filename: &str) -> Result<LevelCollection, LevelParseError> {
+		let input = std::fs::read_to_string(filename);
+		let input = match input {
+			Ok(x) => x,
+			Err(e) => return Err(LevelParseError::FileError { filename: filename.to_string(), message: e.to_string() }),
+		};
+
+		LevelCollection::from_str(&input)
+	}
+	pub fn len(&self) -> usize {
+		self.levels.len()
+	}
+	pub fn is_empty(&self) -> bool {
+		self.levels.is_empty()
+	}
+	pub fn get(&self, idx: usize) -> Option<&Result<Level,LevelParseError>> {
+		self.levels.get(idx)
+	}
+	pub fn levels(&self) -> &Vec<Result<Level,LevelParseError>> {
+		&self.levels
+	}
+}
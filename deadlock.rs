@@ -0,0 +1,112 @@
+// Box Bopper: Sokoban-like game
+// Copyright David Atkinson 2020-2021
+//
+// deadlock.rs: find boxxes that can never get to a hole, whatever the human does next
+
+use crate::level::Level;
+use crate::vector::{Vector,Move};
+use super::Obj;
+
+// Corners of the four 2x2 squares that a point can be part of
+const SQUARE_OFFSETS: [Vector; 4] = [ Vector(0,0), Vector(-1,0), Vector(0,-1), Vector(-1,-1) ];
+
+fn is_boxx(level: &Level, pt: &Vector) -> bool {
+	matches!(level.get_obj_at_pt_checked(pt), Obj::Boxx | Obj::BoxxInHole)
+}
+
+fn is_wall(level: &Level, pt: &Vector, walls: &[Vector]) -> bool {
+	level.get_obj_at_pt_checked(pt) == Obj::Wall || walls.contains(pt)
+}
+
+fn blocked_on_axis(level: &Level, pt: &Vector, dir: &Move, walls: &mut Vec::<Vector>, dead_blocks: bool) -> bool {
+	// can the boxx at pt never be pushed either way along this axis?
+	let a = pt.add_dir(dir);
+	let b = pt.add_dir(&dir.reverse());
+	if is_wall(level, &a, walls) || is_wall(level, &b, walls) {
+		return true;
+	}
+	// pushing it either way would put it on a dead square
+	if dead_blocks && level.in_noboxx_pts(&a) && level.in_noboxx_pts(&b) {
+		return true;
+	}
+	// a neighbouring boxx that is frozen itself (treat this boxx as a wall, so we don't go round in circles)
+	walls.push(*pt);
+	let blocked = [a, b].iter().any(|n| is_boxx(level, n) && is_frozen(level, n, walls, dead_blocks));
+	walls.pop();
+	blocked
+}
+
+fn is_frozen(level: &Level, pt: &Vector, walls: &mut Vec::<Vector>, dead_blocks: bool) -> bool {
+	blocked_on_axis(level, pt, &Move::Left, walls, dead_blocks) && blocked_on_axis(level, pt, &Move::Up, walls, dead_blocks)
+}
+
+fn find_boxxes(level: &Level) -> Vec::<Vector> {
+	let mut boxxes = Vec::<Vector>::new();
+	for y in 0..level.h as i32 {
+		for x in 0..level.w as i32 {
+			if is_boxx(level, &Vector(x,y)) {
+				boxxes.push(Vector(x,y));
+			}
+		}
+	}
+	boxxes
+}
+
+pub fn find_immovable_boxxes(level: &Level) -> Vec::<Vector> {
+	// boxxes (in holes or not) that can't be pushed at all, held in place by walls and each other
+	find_boxxes(level).into_iter().filter(|b| is_frozen(level, b, &mut Vec::new(), false)).collect()
+}
+
+pub fn find_stuck_boxxes(level: &Level) -> Vec::<Vector> {
+	let boxxes = find_boxxes(level);
+	let mut stuck = Vec::<Vector>::new();
+
+	// boxxes on dead squares
+	for b in boxxes.iter() {
+		if level.get_obj_at_pt(b) == Obj::Boxx && level.in_noboxx_pts(b) {
+			stuck.push(*b);
+		}
+	}
+
+	// 2x2 squares of boxxes and walls, with a boxx that isn't in a hole
+	for b in boxxes.iter() {
+		for offset in SQUARE_OFFSETS.iter() {
+			let corner = b.add(offset);
+			let square = [ corner, corner.add(&Vector(1,0)), corner.add(&Vector(0,1)), corner.add(&Vector(1,1)) ];
+			if square.iter().all(|pt| is_wall(level, pt, &[]) || is_boxx(level, pt))
+				&& square.iter().any(|pt| level.get_obj_at_pt_checked(pt) == Obj::Boxx) {
+				stuck.extend(square.iter().filter(|pt| is_boxx(level, pt)));
+			}
+		}
+	}
+
+	// frozen boxxes: blocked both ways by walls, dead squares, or each other
+	// a group of frozen boxxes is only stuck if one of them isn't in a hole
+	let frozen: Vec::<Vector> = boxxes.iter().filter(|b| is_frozen(level, b, &mut Vec::new(), true)).cloned().collect();
+	let mut done = vec![false; frozen.len()];
+	for i in 0..frozen.len() {
+		if done[i] {
+			continue;
+		}
+		done[i] = true;
+		let mut group = vec![ frozen[i] ];
+		let mut gidx = 0;
+		while gidx < group.len() {
+			let pt = group[gidx];
+			for (j, f) in frozen.iter().enumerate() {
+				if !done[j] && (f.0 - pt.0).abs() + (f.1 - pt.1).abs() == 1 {
+					done[j] = true;
+					group.push(*f);
+				}
+			}
+			gidx += 1;
+		}
+		if group.iter().any(|pt| level.get_obj_at_pt(pt) == Obj::Boxx) {
+			stuck.extend(group);
+		}
+	}
+
+	stuck.sort_unstable();
+	stuck.dedup();
+	stuck
+}
@@ -0,0 +1,63 @@
+// Box Bopper: Sokoban-like game
+// Copyright David Atkinson 2020-2021
+//
+// event.rs: things that happen as a Game is played, for front ends to react to (sounds, statistics etc.)
+
+use wasm_bindgen::prelude::*;
+use std::fmt;
+
+use crate::vector::{Vector,Move};
+
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum GameEvent {
+	HumanMoved { from: Vector, to: Vector },
+	BoxPushed { from: Vector, to: Vector },
+	BoxEnteredHole { at: Vector },
+	BoxLeftHole { at: Vector },
+	LevelSolved { moves: u32, pushes: u32 },
+	MoveRejected { mv: Move },
+}
+
+impl fmt::Display for GameEvent {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			GameEvent::HumanMoved { from, to } => write!(f, "Moved from {} to {}", from.to_string(), to.to_string()),
+			GameEvent::BoxPushed { from, to } => write!(f, "Pushed box from {} to {}", from.to_string(), to.to_string()),
+			GameEvent::BoxEnteredHole { at } => write!(f, "Box in hole at {}", at.to_string()),
+			GameEvent::BoxLeftHole { at } => write!(f, "Box out of hole at {}", at.to_string()),
+			GameEvent::LevelSolved { moves, pushes } => write!(f, "Solved in {} moves, {} pushes!", moves, pushes),
+			GameEvent::MoveRejected { mv } => write!(f, "Can't move {}", mv.to_string()),
+		}
+	}
+}
+
+#[wasm_bindgen]
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum EventKind { HumanMoved, BoxPushed, BoxEnteredHole, BoxLeftHole, LevelSolved, MoveRejected }
+
+#[wasm_bindgen]
+#[derive(Clone,Copy)]
+pub struct EventInfo {		// a GameEvent passed back to JS, fields that don't apply to the kind of event are 0
+	pub kind: EventKind,
+	pub x: i32,				// where it happened (or where it came from)
+	pub y: i32,
+	pub to_x: i32,
+	pub to_y: i32,
+	pub moves: u32,
+	pub pushes: u32,
+	pub dir: u32,			// Up=0, Right=1, Down=2, Left=3
+}
+
+impl GameEvent {
+	pub fn to_info(&self) -> EventInfo {
+		let blank = EventInfo { kind: EventKind::HumanMoved, x: 0, y: 0, to_x: 0, to_y: 0, moves: 0, pushes: 0, dir: 0 };
+		match *self {
+			GameEvent::HumanMoved { from, to }       => EventInfo { kind: EventKind::HumanMoved, x: from.0, y: from.1, to_x: to.0, to_y: to.1, ..blank },
+			GameEvent::BoxPushed { from, to }        => EventInfo { kind: EventKind::BoxPushed, x: from.0, y: from.1, to_x: to.0, to_y: to.1, ..blank },
+			GameEvent::BoxEnteredHole { at }         => EventInfo { kind: EventKind::BoxEnteredHole, x: at.0, y: at.1, ..blank },
+			GameEvent::BoxLeftHole { at }            => EventInfo { kind: EventKind::BoxLeftHole, x: at.0, y: at.1, ..blank },
+			GameEvent::LevelSolved { moves, pushes } => EventInfo { kind: EventKind::LevelSolved, moves, pushes, ..blank },
+			GameEvent::MoveRejected { mv }           => EventInfo { kind: EventKind::MoveRejected, dir: mv as u32, ..blank },
+		}
+	}
+}
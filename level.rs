@@ -7,10 +7,12 @@ use wasm_bindgen::prelude::*;
 use js_sys::{Array,JsString};
 
 use std::convert::TryInto;
-use std::collections::HashMap;
+use std::collections::{HashMap,VecDeque};
 use std::string::String;
+use std::fmt;
+use std::ops::{Deref,DerefMut};
 
-use crate::vector::{Vector,VectorSm};
+use crate::vector::{Vector,VectorSm,Move,ALLMOVES,Symmetry,ALLSYMMETRIES};
 use super::Obj;
 use crate::builtins::BUILTIN_LEVELS;
 
@@ -37,56 +39,123 @@ pub fn verify_builtins() -> bool {
 		} else {
 			println!("Warning: Level index {} has no num.", i);
 		}
+		// check that the stored solution actually solves the level
+		if level.contains_key("path") {
+			let path = level.get_keyval("path");
+			match level.path_solves(&path) {
+				Ok(true) => {},
+				Ok(false) => {
+					println!("Level index {} path does not solve the level", i);
+					ok = false;
+				},
+				Err(e) => {
+					println!("Level index {} has a bad path: {}", i, e);
+					ok = false;
+				},
+			}
+			if level.contains_key("moves") && level.get_keyval("moves").parse::<usize>() != Ok(path.len()) {
+				println!("Level index {} has moves {} but path has {} moves", i, level.get_keyval("moves"), path.len());
+				ok = false;
+			}
+		}
 	}
 	ok
 }
 
-#[derive(Copy,Clone,PartialEq,PartialOrd,Ord,Eq,Hash)]
-pub struct CmpData {
-	pub human_x: i8,
-	pub human_y: i8,
-	pub blocks: [u64; 4],
+// One bit per square. Levels of up to 256 squares keep their bits inline (fast, no allocation),
+// bigger levels put them on the heap.
+#[derive(Clone,PartialEq,PartialOrd,Ord,Eq,Hash)]
+pub enum BitBlocks {
+	Inline([u64; 4]),
+	Heap(Box<[u64]>),
 }
 
-impl CmpData {
-	pub fn new() -> CmpData { 
-		CmpData {
-			human_x: 0,
-			human_y: 0,
-			blocks: [0_u64; 4],
+impl BitBlocks {
+	pub fn new(num_bits: usize) -> BitBlocks {
+		if num_bits <= 256 {
+			BitBlocks::Inline([0_u64; 4])
+		} else {
+			BitBlocks::Heap(vec![0_u64; num_bits.div_ceil(64)].into_boxed_slice())
 		}
 	}
-	pub fn from_data(human_pos: &Vector, ldata: &Vec::<Obj>) -> CmpData {
-		let mut cmp_data = CmpData::new();
-		cmp_data.human_x = human_pos.0 as i8;
-		cmp_data.human_y = human_pos.1 as i8;
+	pub fn from_data(ldata: &[Obj], f: impl Fn(&Obj) -> bool) -> BitBlocks {
+		let mut blocks = BitBlocks::new(ldata.len());
 		let mut data: u64 = 0;
 		let mut bits_used: usize = 0;
 		let mut block = 0;
 		for o in ldata.iter() {
 			if bits_used % 64 == 0 && bits_used != 0 {
-				cmp_data.blocks[block] = data;
+				blocks[block] = data;
 				block += 1;
 				data = 0;
 			}
 			data <<= 1;
-			data |= (*o==Obj::Boxx||*o==Obj::BoxxInHole) as u64;
+			data |= f(o) as u64;
 			bits_used += 1;
 		}
 		// align last block
 		if bits_used % 64 != 0 {
-			data = data << (64 - (bits_used % 64));
+			data <<= 64 - (bits_used % 64);
 		}
-		cmp_data.blocks[block] = data;
+		blocks[block] = data;
+		blocks
+	}
+	pub fn print(&self) {
+		for block in self.iter() {
+			for i in 0..63 {
+				print!("{}", (block >> (63-i)) & 1);
+			}
+			println!();
+		}
+	}
+}
+
+impl Deref for BitBlocks {
+	type Target = [u64];
+	#[inline(always)]
+	fn deref(&self) -> &[u64] {
+		match self {
+			BitBlocks::Inline(b) => b,
+			BitBlocks::Heap(b) => b,
+		}
+	}
+}
+
+impl DerefMut for BitBlocks {
+	#[inline(always)]
+	fn deref_mut(&mut self) -> &mut [u64] {
+		match self {
+			BitBlocks::Inline(b) => b,
+			BitBlocks::Heap(b) => b,
+		}
+	}
+}
+
+#[derive(Clone,PartialEq,PartialOrd,Ord,Eq,Hash)]
+pub struct CmpData {
+	pub human_x: i16,
+	pub human_y: i16,
+	pub blocks: BitBlocks,
+}
+
+impl CmpData {
+	pub fn new() -> CmpData { 
+		CmpData {
+			human_x: 0,
+			human_y: 0,
+			blocks: BitBlocks::new(0),
+		}
+	}
+	pub fn from_data(human_pos: &Vector, ldata: &Vec::<Obj>) -> CmpData {
+		let cmp_data = CmpData {
+			human_x: human_pos.0 as i16,
+			human_y: human_pos.1 as i16,
+			blocks: BitBlocks::from_data(ldata, |o| *o==Obj::Boxx || *o==Obj::BoxxInHole),
+		};
 		
 		if false {
 			println!("cmp data:");
-			for block in cmp_data.blocks.iter() {
-				for i in 0..63 {
-					print!("{}", (block >> (63-i)) & 1);
-				}
-				println!();
-			}
+			cmp_data.blocks.print();
 		}
 		
 		cmp_data
@@ -94,29 +163,118 @@ impl CmpData {
 }
 
 
-const X1VARS: [[Vector;3];8] = // only interested in the neighbours (not the opposite)
-[
-[Vector(0,0),Vector(0,1),Vector(1,0)],
-[Vector(0,0),Vector(1,0),Vector(0,1)],
-[Vector(0,1),Vector(0,0),Vector(1,1)],
-[Vector(0,1),Vector(1,1),Vector(0,0)],
-[Vector(1,0),Vector(0,0),Vector(1,1)],
-[Vector(1,0),Vector(1,1),Vector(0,0)],
-[Vector(1,1),Vector(0,1),Vector(1,0)],
-[Vector(1,1),Vector(1,0),Vector(0,1)],
-];
+pub const MAX_BOXXES: usize = 24;		// This is an arbitrary limit, but currently too many boxes uses too many resources
+pub const MAX_WIDTH: u16 = 255;
+pub const MAX_HEIGHT: u16 = 255;
+
+// Errors from reading in a level. line and col start at 1, and refer to the level text.
+#[derive(Clone,PartialEq,Debug)]
+pub enum LevelParseError {
+	UnknownGlyph { line: usize, col: usize, glyph: char },
+	NotEnclosed { line: usize, col: usize },
+	MultipleHumans { line: usize, col: usize },
+	NoHuman,
+	BoxHoleMismatch { boxes: usize, holes: usize },
+	NoBoxes,
+	TooManyBoxes { boxes: usize },
+	TooSmall { w: usize, h: usize },
+	TooBig { w: usize, h: usize },
+	NoSuchBuiltin { number: usize },
+	FileError { filename: String, message: String },
+}
+
+impl fmt::Display for LevelParseError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			LevelParseError::UnknownGlyph { line, col, glyph } => write!(f, "Unknown character '{}' at line {}, column {}", glyph, line, col),
+			LevelParseError::NotEnclosed { line, col } => write!(f, "Level is not walled in (outside at line {}, column {})", line, col),
+			LevelParseError::MultipleHumans { line, col } => write!(f, "More than one human found! (second at line {}, column {})", line, col),
+			LevelParseError::NoHuman => write!(f, "Human not found in level!"),
+			LevelParseError::BoxHoleMismatch { boxes, holes } => write!(f, "Num boxes ({}) is not equal to num holes ({})!", boxes, holes),
+			LevelParseError::NoBoxes => write!(f, "Must be at least one box!"),
+			LevelParseError::TooManyBoxes { boxes } => write!(f, "Too many boxes! ({}, maximum {})", boxes, MAX_BOXXES),
+			LevelParseError::TooSmall { w, h } => write!(f, "Level too small ({} x {})! Width and Height must be at least 1.", w, h),
+			LevelParseError::TooBig { w, h } => write!(f, "Level too big ({} x {})! Maximum width {}. Maximum height {}.", w, h, MAX_WIDTH, MAX_HEIGHT),
+			LevelParseError::NoSuchBuiltin { number } => write!(f, "Level number {} too high, maximum is {}", number, BUILTIN_LEVELS.len() - 1),
+			LevelParseError::FileError { filename, message } => write!(f, "Failed to open level file {}: {}", filename, message),
+		}
+	}
+}
+
+impl LevelParseError {
+	pub fn with_line_offset(self, offset: usize) -> LevelParseError {
+		// used when the level text was part of a larger file
+		match self {
+			LevelParseError::UnknownGlyph { line, col, glyph } => LevelParseError::UnknownGlyph { line: line + offset, col, glyph },
+			LevelParseError::NotEnclosed { line, col } => LevelParseError::NotEnclosed { line: line + offset, col },
+			LevelParseError::MultipleHumans { line, col } => LevelParseError::MultipleHumans { line: line + offset, col },
+			e => e,
+		}
+	}
+}
+
+impl std::error::Error for LevelParseError {}
+
+impl From<LevelParseError> for JsValue {
+	fn from(e: LevelParseError) -> JsValue {
+		js_sys::Error::new(&e.to_string()).into()
+	}
+}
+
+// Level text can be in our own alphabet (#*O&%@) or standard XSB sokoban (#$.@+*)
+#[derive(Clone,Copy,PartialEq,Debug)]
+pub enum LevelFormat { Native, Xsb }
+
+impl LevelFormat {
+	pub fn obj_from_char(&self, c: &char) -> Option<Obj> {
+		match self {
+			LevelFormat::Native => Obj::from_char(c),
+			LevelFormat::Xsb => Obj::from_xsb_char(c),
+		}
+	}
+}
 
 
+// Problems found while replaying a path (e.g. "RRDDLU") against a level
+#[derive(Clone,PartialEq,Debug)]
+pub enum ReplayError {
+	InvalidChar { index: usize, ch: char },
+	IllegalMove { index: usize, mv: Move },
+}
+
+impl fmt::Display for ReplayError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			ReplayError::InvalidChar { index, ch } => write!(f, "Unknown move '{}' at move {}", ch, index + 1),
+			ReplayError::IllegalMove { index, mv } => write!(f, "Move {} ({}) can't be made", index + 1, mv.to_string()),
+		}
+	}
+}
+
+impl std::error::Error for ReplayError {}
+
+pub fn string_to_moves(path: &str) -> Result<Vec::<Move>, ReplayError> {
+	let mut moves = Vec::<Move>::with_capacity(path.len());
+	for (index, ch) in path.chars().enumerate() {
+		match Move::from_char(&ch) {
+			Some(m) => moves.push(m),
+			None => return Err(ReplayError::InvalidChar { index, ch }),
+		}
+	}
+	Ok(moves)
+}
+
 #[wasm_bindgen]
 #[derive(Clone,PartialEq)] //,PartialOrd
 pub struct Level {
 	pub w: u16,
 	pub h: u16,
 	pub human_pos: Vector,
-	win_data: [u64; 4],
+	win_data: BitBlocks,
 	data: Vec::<Obj>,
-	keyvals: HashMap::<String,String>,
+	keyvals: Vec::<(String,String)>,		// in the order they were read in / set, so files come out the same way
 	noboxx_pts: Vec::<Vector>,
+	noboxx_map: Vec::<bool>,		// same as noboxx_pts, by index, for quick lookups
 	boxx_pts: Vec::<Vector>,
 	hole_pts: Vec::<Vector>,
 	wall_pts: Vec::<Vector>,
@@ -126,8 +284,8 @@ pub struct Level {
 
 #[derive(Clone,PartialEq)]
 pub struct SpLevel {
-	pub w: i8,
-	pub h: i8,
+	pub w: i16,
+	pub h: i16,
 	pub cmp_data: CmpData,
 }
 
@@ -135,8 +293,8 @@ pub struct SpLevel {
 impl SpLevel {
 	pub fn from_level(level: &Level) -> Self {		
 		Self {
-			w: level.w as i8,
-			h: level.h as i8,
+			w: level.w as i16,
+			h: level.h as i16,
 			cmp_data: CmpData::from_data(&level.human_pos, &level.data),
 		}
 	}
@@ -211,8 +369,8 @@ impl SpLevel {
 	pub fn set_human_pos(&mut self, pt: &Vector) {
 		// ignores underlying level
 		let cmp_data = &mut self.cmp_data;
-		cmp_data.human_x = pt.0 as i8;
-		cmp_data.human_y = pt.1 as i8;
+		cmp_data.human_x = pt.0 as i16;
+		cmp_data.human_y = pt.1 as i16;
 	}
 	pub fn get_human_pos(&self) -> Vector {
 		// ignores underlying level
@@ -245,11 +403,7 @@ impl SpLevel {
 impl Level {
 	#[wasm_bindgen]
 	pub fn from_builtin_js(number: usize) -> Result<Level, JsValue> {
-		let level = Level::from_builtin(number);
-		return match level {
-			Ok(l) => Ok(l),
-			Err(s) => Err(JsValue::from_str(s)),
-		};
+		Ok(Level::from_builtin(number)?)
 	}
 	pub fn get_obj_at_pt(&self, pt: &Vector) -> Obj {
 		self.data[(pt.0 as usize) + (pt.1 as usize) * (self.w as usize)]
@@ -280,30 +434,6 @@ impl Level {
 	pub fn get_data(&self) -> Array {
 		self.data.clone().into_iter().map(|obj| JsValue::from(obj as u32)).collect()
 	}
-	fn get_vslice(&self, x: isize, y0: isize, y1: isize) -> Vec::<Obj> {
-		let mut rv = Vec::<Obj>::new();
-		for i in y0..y1 {
-			let v = Vector(x as i32,i as i32);
-			if self.vector_in_bounds(&v) {
-				rv.push(self.get_obj_at_pt(&Vector(x as i32,i as i32)));
-			} else {
-				rv.push(Obj::Wall);
-			}
-		}
-		return rv;
-	}
-	fn get_hslice(&self, x0: isize, x1: isize, y: isize) -> Vec::<Obj> {
-		let mut rv = Vec::<Obj>::new();
-		for i in x0..x1 {
-			let v = Vector(i as i32,y as i32);
-			if self.vector_in_bounds(&v) {
-				rv.push(self.get_obj_at_pt(&Vector(i as i32,y as i32)));
-			} else {
-				rv.push(Obj::Wall);
-			}
-		}
-		return rv;
-	}
 	pub fn get_level_width(&self) -> u32 {
 		self.w as u32
 	}
@@ -311,9 +441,7 @@ impl Level {
 		self.h as u32
 	} 
 	pub fn get_title(&self) -> JsString {
-		let s = self.keyvals.get(&"title".to_string());
-		let s2 = s.unwrap_or(&"untitled".to_string()).to_string();
-		return JsString::from(s2);
+		return JsString::from(self.get_title_str());
 	}
 	pub fn have_win_condition(&self) -> bool {
 		for obj in self.data.iter() {
@@ -324,112 +452,297 @@ impl Level {
 		}
 		return true;
 	}
+	pub fn rotate_cw(&self) -> Level {
+		self.transform(Symmetry::RotateCw)
+	}
+	pub fn rotate_ccw(&self) -> Level {
+		self.transform(Symmetry::RotateCcw)
+	}
+	pub fn flip_h(&self) -> Level {
+		self.transform(Symmetry::FlipH)
+	}
+	pub fn flip_v(&self) -> Level {
+		self.transform(Symmetry::FlipV)
+	}
+	pub fn transpose(&self) -> Level {
+		self.transform(Symmetry::Transpose)
+	}
 }
 
 // non-js
 impl Level {
-	pub fn from_builtin(number: usize) -> Result<Level, &'static str> {
+	pub fn transform(&self, sym: Symmetry) -> Level {
+		// returns a rotated and / or mirrored copy of the level
+		let (w, h) = if sym.swaps_dims() { (self.h, self.w) } else { (self.w, self.h) };
+		let mut level = self.clone();
+		level.w = w;
+		level.h = h;
+		for y in 0..self.h as i32 {
+			for x in 0..self.w as i32 {
+				let pt = Vector(x, y);
+				let npt = sym.map_pt(&pt, self.w, self.h);
+				level.data[npt.to_index(w)] = self.get_obj_at_pt(&pt);
+			}
+		}
+		level.human_pos = sym.map_pt(&self.human_pos, self.w, self.h);
+		level.do_noboxx_pts();
+		level.do_boxx_pts();
+		level.make_win_data();
+
+		// the stored solution has to turn the same way (keeping upper / lower case for pushes)
+		if let Some(path) = self.find_keyval("path") {
+			let npath: String = path.chars().map(|c| match Move::from_char(&c) {
+				Some(m) => {
+					let nc = sym.map_move(&m).to_string().chars().next().unwrap();
+					if c.is_lowercase() { nc.to_ascii_lowercase() } else { nc }
+				},
+				None => c,
+			}).collect();
+			level.set_keyval("path", &npath);
+		}
+		level
+	}
+	pub fn reachable(&self, from: &Vector, through_boxxes: bool) -> Vec::<bool> {
+		// flood fill the cells the human can get to, optionally treating boxxes as if they could all be pushed out of the way
+		let mut visited = vec![false; self.w as usize * self.h as usize];
+		let mut queue = VecDeque::<Vector>::new();
+		visited[from.to_index(self.w)] = true;
+		queue.push_back(*from);
+		while let Some(pt) = queue.pop_front() {
+			for movedir in ALLMOVES.iter() {
+				let npt = pt.add_dir(movedir);
+				if !self.vector_in_bounds(&npt) || visited[npt.to_index(self.w)] {
+					continue;
+				}
+				let passable = match self.get_obj_at_pt(&npt) {
+					Obj::Space | Obj::Hole => true,
+					Obj::Boxx | Obj::BoxxInHole => through_boxxes,
+					_ => false,
+				};
+				if passable {
+					visited[npt.to_index(self.w)] = true;
+					queue.push_back(npt);
+				}
+			}
+		}
+		visited
+	}
+	pub fn normalize(&self) -> Level {
+		// wall in the floor the human can never get to, then trim the outer walls (the outer ring is always implied)
+		// moves stay the same, so a stored path still works
+		let reach = self.reachable(&self.human_pos, true);
+		let mut filled = self.data.clone();
+		for (i, o) in filled.iter_mut().enumerate() {
+			if !reach[i] && *o == Obj::Space {
+				*o = Obj::Wall;
+			}
+		}
+		let (mut x0, mut y0, mut x1, mut y1) = (self.w as usize, self.h as usize, 0, 0);
+		for y in 0..self.h as usize {
+			for x in 0..self.w as usize {
+				if filled[y * self.w as usize + x] != Obj::Wall {
+					x0 = x0.min(x);
+					y0 = y0.min(y);
+					x1 = x1.max(x);
+					y1 = y1.max(y);
+				}
+			}
+		}
+		let mut data = Vec::<Obj>::with_capacity((x1 - x0 + 1) * (y1 - y0 + 1));
+		for y in y0..=y1 {
+			data.extend_from_slice(&filled[y * self.w as usize + x0 ..= y * self.w as usize + x1]);
+		}
+		let mut level = self.clone();
+		level.w = (x1 - x0 + 1) as u16;
+		level.h = (y1 - y0 + 1) as u16;
+		level.data = data;
+		level.human_pos = Vector(self.human_pos.0 - x0 as i32, self.human_pos.1 - y0 as i32);
+		level.do_noboxx_pts();
+		level.do_boxx_pts();
+		level.make_win_data();
+		level
+	}
+	fn canonical_string(&self) -> String {
+		// the human can walk anywhere in its area without pushing, so always put it in the top-left-most spot
+		let mut level = self.clone();
+		let reach = level.reachable(&level.human_pos, false);
+		let idx = reach.iter().position(|r| *r).unwrap();
+		level.clear_human();
+		level.human_pos = Vector((idx % level.w as usize) as i32, (idx / level.w as usize) as i32);
+		level.place_human();
+		level.to_string()
+	}
+	pub fn canonical_hash(&self) -> u64 {
+		// fingerprint that is the same for levels that are the same puzzle, even if rotated, mirrored or padded out
+		// takes the smallest of the eight orientations, then FNV-1a hashes it
+		let cropped = self.normalize();
+		let s = ALLSYMMETRIES.iter().map(|sym| cropped.transform(*sym).canonical_string()).min().unwrap();
+		let mut hash: u64 = 0xcbf29ce484222325;
+		for b in s.bytes() {
+			hash ^= b as u64;
+			hash = hash.wrapping_mul(0x100000001b3);
+		}
+		hash
+	}
+	pub fn from_builtin(number: usize) -> Result<Level, LevelParseError> {
 		// locate string
 		if number >= BUILTIN_LEVELS.len() {
-			return Err("Level number too high");
+			return Err(LevelParseError::NoSuchBuiltin { number });
 		}
 		
 		let level = BUILTIN_LEVELS[number];
 		Level::from_str(level)
 	}
-	pub fn from_str(level_str: &str) -> Result<Level, &str> {
-		let mut count: usize = 0;
-		let mut h: u16 = 0;
-		let mut w: u16 = 0;
-		let mut data = Vec::<Obj>::with_capacity(128);
+	pub fn is_keyval_line(line: &str) -> bool {
+		// e.g. "title: something" or "Author: someone". Level rows never contain ':'
+		match line.find(':') {
+			Some(idx) => {
+				let key = line[0..idx].trim();
+				key.len() > 0 && key.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == ' ')
+			},
+			None => false,
+		}
+	}
+	fn grid_lines(level_str: &str) -> impl Iterator<Item=(usize,&str)> {
+		// the rows of the level (with their line index): from the first non-blank line, up to a blank line or a key: value line
+		level_str.lines().enumerate()
+			.skip_while(|(_,l)| l.trim().len() == 0)
+			.take_while(|(_,l)| l.trim().len() != 0 && !Level::is_keyval_line(l))
+	}
+	pub fn detect_format(level_str: &str) -> LevelFormat {
+		// & % O only appear in our own format, $ . + - _ only in XSB. # * @ and space are in both.
+		// Only look at the grid, as the keyvals can contain anything.
+		let mut xsb = false;
+		for (_,line) in Level::grid_lines(level_str) {
+			if line.contains(|c| c == '&' || c == '%' || c == 'O') {
+				return LevelFormat::Native;
+			}
+			xsb |= line.contains(|c| c == '$' || c == '.' || c == '+' || c == '-' || c == '_' || c == '@');
+		}
+		if xsb { LevelFormat::Xsb } else { LevelFormat::Native }
+	}
+	pub fn from_str(level_str: &str) -> Result<Level, LevelParseError> {
+		Level::from_str_with_format(level_str, Level::detect_format(level_str))
+	}
+	pub fn from_xsb(level_str: &str) -> Result<Level, LevelParseError> {
+		Level::from_str_with_format(level_str, LevelFormat::Xsb)
+	}
+	pub fn from_str_with_format(level_str: &str, format: LevelFormat) -> Result<Level, LevelParseError> {
+		let mut rows = Vec::<Vec::<Obj>>::new();
+		let mut row_lines = Vec::<usize>::new();		// line index of each row, for error messages
+		let mut kv_start: usize = 0;
+		let mut keyvals = Vec::<(String,String)>::new();
 		let mut human_pos: Option<Vector> = None;
-		let mut keyvals = HashMap::new();
-		let mut kvmode = false;
 		let mut num_boxxes = 0;
 		let mut num_holes = 0;
-	
-		for line in level_str.lines() {		
-			let txt = line;
-			if count == 0 {
-				// read in length
-				w = txt.len() as u16;			
+
+		// read in the rows, which don't have to be the same length
+		for (count, line) in Level::grid_lines(level_str) {
+			let mut row = Vec::<Obj>::with_capacity(line.len());
+			for (i,c) in line.chars().enumerate() {
+				match format.obj_from_char(&c) {
+					Some(o) => row.push(o),
+					None => return Err(LevelParseError::UnknownGlyph { line: count + 1, col: i + 1, glyph: c }),
+				};
 			}
-			// check length equal to w
-			if !kvmode && txt.len() == w as usize {	
-				// split line into characters
-				for (i,c) in txt.char_indices() {		// chars() is iterator
-					if c == '&' || c == '%' {
-						// found human_pos
-						if human_pos.is_none() {
-							human_pos = Some(Vector(i.try_into().unwrap(),h.try_into().unwrap()));
-						} else {
-							return Err("More than one human found!");
-						}
-					}
-					if c == 'O' || c == '%' || c == '@' {
-						num_holes += 1;
-					}
-					if c == '*' || c == '@' {
-						num_boxxes += 1;
-					}
-					data.push( Obj::from_char(&c) );
-				}
-				h += 1;
-			} else {
-				kvmode = true;
-				// read in key and vals
-				// left of ':', right of ':', strip whitespace at front and end
-				if txt.len() >= 2 {
-					let idx = txt.find(':');
-					if idx.is_some() {
-						let idx = idx.unwrap();
-						let left = &txt[0..idx].trim();
-						let right = &txt[idx+1..].trim();
-						if left.len() > 0 {
-							keyvals.insert(left.to_string(),right.to_string());
-						}
+			rows.push(row);
+			row_lines.push(count);
+			kv_start = count + 1;
+		}
+
+		// everything after the rows is keyvals
+		// left of ':', right of ':', strip whitespace at front and end
+		// keys keep their spelling, but are matched ignoring case, as XSB files tend to use Title: and Author:
+		for txt in level_str.lines().skip(kv_start) {
+			if let Some(idx) = txt.find(':') {
+				let left = &txt[0..idx].trim();
+				let right = &txt[idx+1..].trim();
+				if left.len() > 0 {
+					// a repeated key replaces the earlier value, but keeps its place (and spelling)
+					match keyvals.iter_mut().find(|(k,_)| k.eq_ignore_ascii_case(left)) {
+						Some((_,v)) => *v = right.to_string(),
+						None => keyvals.push((left.to_string(), right.to_string())),
 					}
 				}
 			}
-			
-			count += 1;
-		}
-	
-		// remove the borders
-		let mut tdata = Vec::<Obj>::new();
-		for y in 1..(h-1) as usize {
-			for x in 1..(w-1) as usize {
-				tdata.push(data[y*w as usize+x]);
+		}
+
+		// pad out the short rows
+		let w = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+		let h = rows.len();
+		if w < 3 || h < 3 {
+			return Err(LevelParseError::TooSmall { w: w.saturating_sub(2), h: h.saturating_sub(2) });
+		}
+		if w - 2 > MAX_WIDTH as usize || h - 2 > MAX_HEIGHT as usize {
+			return Err(LevelParseError::TooBig { w: w - 2, h: h - 2 });
+		}
+		for row in rows.iter_mut() {
+			row.resize(w, Obj::Space);
+		}
+
+		// the floor outside the outer wall becomes wall, finding anything else out there is an error
+		let mut todo = Vec::<(usize,usize)>::new();
+		for y in 0..h {
+			for x in 0..w {
+				if x == 0 || y == 0 || x == w-1 || y == h-1 {
+					todo.push((x,y));
+				}
 			}
 		}
-		let data = tdata;		
-		w -= 2;
-		h -= 2;
-		if human_pos.is_none() {
-			return Err("Human not found in level!");
+		while let Some((x,y)) = todo.pop() {
+			match rows[y][x] {
+				Obj::Wall => {},
+				Obj::Space => {
+					rows[y][x] = Obj::Wall;
+					if x > 0 { todo.push((x-1,y)); }
+					if y > 0 { todo.push((x,y-1)); }
+					if x < w-1 { todo.push((x+1,y)); }
+					if y < h-1 { todo.push((x,y+1)); }
+				},
+				_ => return Err(LevelParseError::NotEnclosed { line: row_lines[y] + 1, col: x + 1 }),
+			}
 		}
-		let	human_pos = human_pos.unwrap().add(&Vector(-1,-1));
 
-		if w < 1 || h < 1 {
-			//println!("Dimensions: {} x {}", w, h);
-			return Err("Width and Height must be at least 1!");
+		// remove the borders (which are all wall now)
+		let mut data = Vec::<Obj>::with_capacity((w-2) * (h-2));
+		for y in 1..h-1 {
+			for x in 1..w-1 {
+				let obj = rows[y][x];
+				if obj == Obj::Human || obj == Obj::HumanInHole {
+					// found human_pos
+					if human_pos.is_none() {
+						human_pos = Some(Vector(x as i32 - 1, y as i32 - 1));
+					} else {
+						return Err(LevelParseError::MultipleHumans { line: row_lines[y] + 1, col: x + 1 });
+					}
+				}
+				if obj == Obj::Hole || obj == Obj::HumanInHole || obj == Obj::BoxxInHole {
+					num_holes += 1;
+				}
+				if obj == Obj::Boxx || obj == Obj::BoxxInHole {
+					num_boxxes += 1;
+				}
+				data.push(obj);
+			}
+		}
+		let w = (w - 2) as u16;
+		let h = (h - 2) as u16;
+		if human_pos.is_none() {
+			return Err(LevelParseError::NoHuman);
 		}
-		if w > 127 || h > 127 || w * h > 256 {
-			//println!("Dimensions: {} x {}", w, h);
-			return Err("Level too big! Maximum width 127. Maximum height 127. Maximum width * height 256.");
-		} 		
+		let	human_pos = human_pos.unwrap();
 
 		// Check for unequal boxes / holes
 		if num_boxxes != num_holes {
-			return Err("Num boxes is not equal to num holes!");
+			return Err(LevelParseError::BoxHoleMismatch { boxes: num_boxxes, holes: num_holes });
 		}
 
 		if num_boxxes < 1 {
-			return Err("Must be at least one box!");
+			return Err(LevelParseError::NoBoxes);
 		}
 
-		if num_boxxes > 24 {		// This is an arbitrary limit, but currently too many boxes uses too many resources
-			return Err("Too many boxes! (Maximum 24)");
+		if num_boxxes > MAX_BOXXES {
+			return Err(LevelParseError::TooManyBoxes { boxes: num_boxxes });
 		}
 
 		let mut level = Level {
@@ -438,10 +751,11 @@ impl Level {
 			h: h,
 			human_pos: human_pos,
 			noboxx_pts: Vec::new(),
+			noboxx_map: Vec::new(),
 			boxx_pts: Vec::new(),
 			hole_pts: Vec::new(),
 			wall_pts: Vec::new(),
-			win_data: [0_u64; 4],
+			win_data: BitBlocks::new(0),
 			data: data,
 			cleared_of_human: false,
 		};
@@ -450,34 +764,31 @@ impl Level {
 		level.do_boxx_pts();
 		return Ok(level);
 	}
-	pub fn from_file(filename: &str) -> Result<Level, String> {
+	pub fn from_file(filename: &str) -> Result<Level, LevelParseError> {
 		let input = std::fs::read_to_string(filename);
 		let input = match input {
 			Ok(x) => x,
-			_ => return Err("Failed to open level file.".to_string()),
+			Err(e) => return Err(LevelParseError::FileError { filename: filename.to_string(), message: e.to_string() }),
 		};
 			
-		let level = Level::from_str(&input);
-		return match level {
-			Ok(l) => Ok(l),
-			Err(s) => Err(s.to_string()),
-		};
+		Level::from_str(&input)
 	}
 	pub fn from_parts(title: String, w: u16, h: u16, human_pos: Vector, data: Vec::<Obj>) -> Level {
 		let mut level = Level {
-			keyvals: HashMap::new(),
+			keyvals: Vec::new(),
 			w: w as u16,
 			h: h as u16,
 			human_pos: human_pos,
 			noboxx_pts: Vec::new(),
+			noboxx_map: Vec::new(),
 			boxx_pts: Vec::new(),
 			hole_pts: Vec::new(),
 			wall_pts: Vec::new(),
 			data: data,
-			win_data: [0_u64; 4],
+			win_data: BitBlocks::new(0),
 			cleared_of_human: false,
 		};
-		level.keyvals.insert("title".to_string(), title);
+		level.set_keyval("title", &title);
 		if level.confirm_no_human() {
 			level.place_human();
 		}
@@ -488,24 +799,51 @@ impl Level {
 		level
 	}
 	pub fn get_title_str(&self) -> String {
-		return self.keyvals.get(&"title".to_string()).unwrap_or(&"untitled".to_string()).to_string();
+		self.get_keyval_or("title", "untitled")
+	}
+	fn find_keyval(&self, key: &str) -> Option<&String> {
+		// keys are matched ignoring case
+		self.keyvals.iter().find(|(k,_)| k.eq_ignore_ascii_case(key)).map(|(_,v)| v)
 	}
 	pub fn contains_key(&self, key: &str) -> bool {
-		self.keyvals.contains_key(key)
+		self.find_keyval(key).is_some()
 	}
 	pub fn get_keyval(&self, key: &str) -> String {
-		let s = self.keyvals.get(key).unwrap();
+		let s = self.find_keyval(key).unwrap();
 		s.to_string()
 	}
 	pub fn get_keyval_or(&self, key: &str, ors: &str) -> String {
-		let s = self.keyvals.get(key);
+		let s = self.find_keyval(key);
 		if s.is_some() {
 			return s.unwrap().to_string();
 		}
 		ors.to_string()
 	}
 	pub fn set_keyval(&mut self, key: &str, val: &str) {
-		self.keyvals.insert(key.to_string(),val.to_string());
+		// changing a key keeps its place, new keys go on the end
+		match self.keyvals.iter_mut().find(|(k,_)| k.eq_ignore_ascii_case(key)) {
+			Some((_,v)) => *v = val.to_string(),
+			None => self.keyvals.push((key.to_string(),val.to_string())),
+		}
+	}
+	pub fn remove_keyval(&mut self, key: &str) -> Option<String> {
+		let idx = self.keyvals.iter().position(|(k,_)| k.eq_ignore_ascii_case(key))?;
+		Some(self.keyvals.remove(idx).1)
+	}
+	pub fn clear_keyvals(&mut self) {
+		self.keyvals.clear();
+	}
+	pub fn keyvals_to_string(&self) -> String {
+		let mut s = String::new();
+		for (k,v) in self.keyvals.iter() {
+			s += &format!("{}: {}\n", k, v);
+		}
+		s
+	}
+	pub fn to_file_string(&self) -> String {
+		// the grid, then the keyvals, separated by a blank line (so a keyval can't be mistaken for a row)
+		// from_str() reads this back in to the same level, and the same text (values are trimmed, keys keep their case)
+		format!("{}\n{}", self.to_string(), self.keyvals_to_string())
 	}
 	pub fn confirm_no_human(&self) -> bool {
 		for y in 0..self.h as i32 {
@@ -581,114 +919,35 @@ impl Level {
 		}
 	}
 	pub fn do_noboxx_pts(&mut self) {
-		let mut noboxx_pts: Vec::<Vector> = Vec::new();
-		// aside from #, there are some points where box's simply can't go
-		// e.g. the 2x2 [*#][# ] in any orientation (where space could be human too)
-
-		self.clear_human();
-
-		let block_match = [Obj::Space,Obj::Wall,Obj::Wall];
-		for y in -1..=self.h as isize {
-			for x in -1..=self.w as isize {
-				for z in &X1VARS {
-					let pt = Vector(x as i32,y as i32);
-					let objs = [ self.get_obj_at_pt_checked(&pt.add(&z[0])), 
-								 self.get_obj_at_pt_checked(&pt.add(&z[1])), 
-								 self.get_obj_at_pt_checked(&pt.add(&z[2])), ];
-					if objs == block_match {
-						noboxx_pts.push(pt.add(&z[0]));
-					} 
-				}
-			}
-		} 
-
-		self.noboxx_pts = noboxx_pts;
-	
-		// walls that follow the following pattern also can't have boxxes
-		//  ##...# 
-		// #      #
-
-		let hall_start = vec![ Obj::Wall, Obj::Space, Obj::Space, Obj::Space ];
-		let hall_len: isize = 4;
-
-		struct HallInfoH {
-			x: isize,
-			y: isize,
-			end_x: isize,
-		}
-
-		let mut start_x: Option<isize> = None;
-		let mut halls = Vec::<HallInfoH>::new();
-
-		// find the hall '#   '+
-		for y in -1..=self.h as isize {
-			for x in -1..=self.w as isize {
-				let obj_here = self.get_obj_at_pt_checked(&Vector(x as i32,y as i32));
-				if start_x.is_some() && obj_here == Obj::Space { 					// Continuation of hallway
-					// do nothing
-				} else if start_x.is_some() && obj_here == Obj::Wall {				// We have end of the hall					
-					halls.push( HallInfoH { x:start_x.unwrap(), y: y, end_x:x } );
-					start_x = None;
-				} else if start_x.is_some() {										// Not a real hallway
-					start_x = None;
-				}
-				if start_x.is_none() {
-					if hall_start.starts_with(&self.get_hslice(x, x + hall_len, y)) {
-						start_x = Some(x);
-					}
-				}
-			}
-		} 
-		
-		// check if the hall is a valid hall (has a complete wall on one side)
-		for h in halls {
-			let range1 = self.get_hslice(h.x+1,h.end_x,h.y-1);
-			let range2 = self.get_hslice(h.x+1,h.end_x,h.y+1);
-			if range1.iter().all(|o| o==&Obj::Wall) || range2.iter().all(|o| o==&Obj::Wall) {
-				(h.x+1..h.end_x).into_iter().for_each( |x| self.noboxx_pts.push(Vector(x as i32, h.y as i32)));
+		// aside from #, there are some points where a boxx can never get to a hole, even if it had no other boxxes in the way
+		// e.g. corners, halls along a wall, rooms with no holes
+		// work backwards from the holes: pulling a boxx from pt to pt+d needs the human at pt+d, with room to step back to pt+2d
+		// any square we can't pull a boxx to is dead
+		let size = self.w as usize * self.h as usize;
+		let mut live = vec![false; size];
+		let mut queue = VecDeque::<Vector>::new();
+		for (i, o) in self.data.iter().enumerate() {
+			if *o == Obj::Hole || *o == Obj::HumanInHole || *o == Obj::BoxxInHole {
+				live[i] = true;
+				queue.push_back(Vector((i % self.w as usize) as i32, (i / self.w as usize) as i32));
 			}
 		}
-
-		// now do it all vertically!
-		struct HallInfoV {
-			x: isize,
-			y: isize,
-			end_y: isize,
-		}
-		let mut start_y: Option<isize> = None;
-		let mut halls = Vec::<HallInfoV>::new();
-
-		// find the hall '    '+
-		for x in -1..=self.w as isize {
-			for y in -1..=self.h as isize {
-				let obj_here = self.get_obj_at_pt_checked(&Vector(x as i32,y as i32));
-				if start_y.is_some() && (obj_here == Obj::Space) { 	// Continuation of hallway
-					// do nothing
-				} else if start_y.is_some() && obj_here == Obj::Wall {				// We have end of the hall					
-					halls.push( HallInfoV { x:x, y:start_y.unwrap(), end_y:y } );
-					start_y = None;
-				} else if start_y.is_some() {										// Not a real hallway
-					start_y = None;
+		while let Some(pt) = queue.pop_front() {
+			for movedir in ALLMOVES.iter() {
+				let npt = pt.add_dir(movedir);
+				if self.get_obj_at_pt_checked(&npt) == Obj::Wall || self.get_obj_at_pt_checked(&pt.add_dir2(movedir)) == Obj::Wall {
+					continue;
 				}
-				if start_y.is_none() {
-					if hall_start.starts_with(&self.get_vslice(x, y, y + hall_len)) {		// Start of the hallway
-						start_y = Some(y);
-					}
+				if !live[npt.to_index(self.w)] {
+					live[npt.to_index(self.w)] = true;
+					queue.push_back(npt);
 				}
 			}
-		} 
-
-		// check if the hall is a valid hall (has a complete wall on one side)
-		for h in halls {
-			let range1 = self.get_vslice(h.x-1, h.y+1, h.end_y);
-			let range2 = self.get_vslice(h.x+1, h.y+1, h.end_y);
-			if range1.iter().all(|o| o==&Obj::Wall) || range2.iter().all(|o| o==&Obj::Wall) {
-				(h.y+1..h.end_y).into_iter().for_each( |y| self.noboxx_pts.push(Vector(h.x as i32, y as i32)));
-			}
 		}
 
-		self.noboxx_pts.sort_unstable();
-		self.noboxx_pts.dedup();
+		self.noboxx_map = (0..size).map(|i| !live[i] && self.data[i] != Obj::Wall).collect();
+		self.noboxx_pts = (0..size).filter(|i| self.noboxx_map[*i])
+			.map(|i| Vector((i % self.w as usize) as i32, (i / self.w as usize) as i32)).collect();
 		if false {
 			print!("noboxx pts: ");
 			for p in &self.noboxx_pts {
@@ -696,7 +955,6 @@ impl Level {
 			}
 			println!("");
 		}
-		self.place_human();
 	}
 	pub fn do_boxx_pts(&mut self) {
 		let mut bpts: Vec::<Vector> = Vec::new();
@@ -738,14 +996,10 @@ impl Level {
 		count
 	}
 	pub fn in_noboxx_pts(&self, v: &Vector) -> bool {
-		self.noboxx_pts.contains(v)
+		self.vector_in_bounds(v) && self.noboxx_map[v.to_index(self.w)]
 	}
 	pub fn in_noboxx_pts8(&self, v: &VectorSm) -> bool {
-		//self.noboxx_pts.contains(&v.intov())
-		match self.noboxx_pts.binary_search(&v.intov()) {
-			Ok(_) => true,
-			_ => false,
-		}
+		self.vector_in_bounds8(v) && self.noboxx_map[v.to_index(self.w)]
 	}
 	pub fn in_boxx_pts(&self, v: &Vector) -> bool {
 		self.boxx_pts.contains(v)
@@ -784,6 +1038,161 @@ impl Level {
 			self.set_obj_at_idx(idx,nobj);
 		}		
 	}
+	pub fn do_move(&mut self, _move: &Move) -> Option<bool> {
+		// moves the human one step, pushing a boxx if there is one in the way
+		// returns None if the move is not allowed, otherwise whether a boxx was pushed
+		let hp = self.human_pos;
+		let np = hp.add(&_move.to_vector());
+		let bp = np.add(&_move.to_vector());
+		let pushed = match self.get_obj_at_pt_checked(&np) {
+			Obj::Space | Obj::Hole => false,
+			Obj::Boxx | Obj::BoxxInHole => match self.get_obj_at_pt_checked(&bp) {
+				// What's past the boxx? We can push into Space and Hole, nothing else.
+				Obj::Space | Obj::Hole => true,
+				_ => return None,
+			},
+			_ => return None,
+		};
+		if pushed {
+			let obj = self.get_obj_at_pt(&np).without_sprite();
+			self.set_obj_at_pt(&np, obj);
+			let obj = self.get_obj_at_pt(&bp).with_boxx();
+			self.set_obj_at_pt(&bp, obj);
+		}
+		let obj = self.get_obj_at_pt(&hp).without_sprite();
+		self.set_obj_at_pt(&hp, obj);
+		let obj = self.get_obj_at_pt(&np).with_human();
+		self.set_obj_at_pt(&np, obj);
+		self.human_pos = np;
+		Some(pushed)
+	}
+	pub fn walk_path(&self, from: &Vector, to: &Vector) -> Option<Vec::<Move>> {
+		// breadth-first search for the shortest walk between two points, without pushing any boxxes
+		if !self.vector_in_bounds(from) || !self.vector_in_bounds(to) {
+			return None;
+		}
+		let mut came_by: Vec::<Option<Move>> = vec![None; self.w as usize * self.h as usize];
+		let mut visited = vec![false; self.w as usize * self.h as usize];
+		let mut queue = VecDeque::<Vector>::new();
+		visited[from.to_index(self.w)] = true;
+		queue.push_back(*from);
+		while let Some(pt) = queue.pop_front() {
+			if pt == *to {
+				break;
+			}
+			for movedir in ALLMOVES.iter() {
+				let npt = pt.add_dir(movedir);
+				if !self.vector_in_bounds(&npt) || visited[npt.to_index(self.w)] {
+					continue;
+				}
+				if let Obj::Space | Obj::Hole = self.get_obj_at_pt(&npt) {
+					visited[npt.to_index(self.w)] = true;
+					came_by[npt.to_index(self.w)] = Some(*movedir);
+					queue.push_back(npt);
+				}
+			}
+		}
+		if !visited[to.to_index(self.w)] {
+			return None;
+		}
+
+		// follow the moves back from the target to where we started
+		let mut path = Vec::<Move>::new();
+		let mut pt = *to;
+		while pt != *from {
+			let movedir = came_by[pt.to_index(self.w)].unwrap();
+			path.push(movedir);
+			pt = pt.add_dir(&movedir.reverse());
+		}
+		path.reverse();
+		Some(path)
+	}
+	pub fn push_path(&self, human: &Vector, boxx: &Vector, target: &Vector) -> Option<Vec::<Move>> {
+		// plan the walks and pushes to move one boxx to target, without moving any other boxx
+		// breadth-first search over (boxx position, human position after each push), so we get the fewest pushes
+		match self.get_obj_at_pt_checked(boxx) {
+			Obj::Boxx | Obj::BoxxInHole => {},
+			_ => return None,
+		}
+		if !self.vector_in_bounds(target) {
+			return None;
+		}
+
+		// the level without the human or this boxx, so we can put the boxx wherever we are looking
+		let mut floor = self.clone();
+		floor.set_obj_at_pt(human, floor.get_obj_at_pt(human).without_sprite());
+		floor.set_obj_at_pt(boxx, floor.get_obj_at_pt(boxx).without_sprite());
+
+		// for each state, the state we came from and the moves to get here from there
+		let mut came_from = HashMap::<(Vector,Vector),((Vector,Vector),Vec::<Move>)>::new();
+		let mut queue = VecDeque::<(Vector,Vector)>::new();
+		let start = (*boxx, *human);
+		queue.push_back(start);
+		let mut end = None;
+		while let Some((b, h)) = queue.pop_front() {
+			if b == *target {
+				end = Some((b, h));
+				break;
+			}
+			floor.set_obj_at_pt(&b, floor.get_obj_at_pt(&b).with_boxx());
+			for movedir in ALLMOVES.iter() {
+				let stand_pt = b.add_dir(&movedir.reverse());		// where the human pushes from
+				let dest_pt = b.add_dir(movedir);					// where the boxx ends up
+				if !floor.vector_in_bounds(&dest_pt) || came_from.contains_key(&(dest_pt, b)) {
+					continue;
+				}
+				if let Obj::Space | Obj::Hole = floor.get_obj_at_pt(&dest_pt) {
+					if let Some(mut moves) = floor.walk_path(&h, &stand_pt) {
+						moves.push(*movedir);
+						came_from.insert((dest_pt, b), ((b, h), moves));
+						queue.push_back((dest_pt, b));
+					}
+				}
+			}
+			floor.set_obj_at_pt(&b, floor.get_obj_at_pt(&b).without_sprite());
+		}
+
+		// follow the states back to the start, collecting the moves
+		let mut state = end?;
+		let mut segments = Vec::<&Vec::<Move>>::new();
+		while state != start {
+			let (prev, moves) = &came_from[&state];
+			segments.push(moves);
+			state = *prev;
+		}
+		Some(segments.iter().rev().flat_map(|m| m.iter().copied()).collect())
+	}
+	pub fn apply_path(&self, path: &str) -> Result<Level, ReplayError> {
+		// make each move in turn, stopping at the first one that can't be made
+		let mut level = self.clone();
+		for (index, mv) in string_to_moves(path)?.into_iter().enumerate() {
+			if level.do_move(&mv).is_none() {
+				return Err(ReplayError::IllegalMove { index, mv });
+			}
+		}
+		Ok(level)
+	}
+	pub fn path_solves(&self, path: &str) -> Result<bool, ReplayError> {
+		Ok(self.apply_path(path)?.have_win_condition())
+	}
+	pub fn undo_move(&mut self, _move: &Move, pushed: bool) {
+		// reverses a move previously made with do_move()
+		let hp = self.human_pos;
+		let pp = hp.add(&_move.reverse().to_vector());
+		let obj = self.get_obj_at_pt(&hp).without_sprite();
+		self.set_obj_at_pt(&hp, obj);
+		if pushed {
+			// pull the boxx back to where the human was standing
+			let bp = hp.add(&_move.to_vector());
+			let obj = self.get_obj_at_pt(&bp).without_sprite();
+			self.set_obj_at_pt(&bp, obj);
+			let obj = self.get_obj_at_pt(&hp).with_boxx();
+			self.set_obj_at_pt(&hp, obj);
+		}
+		let obj = self.get_obj_at_pt(&pp).with_human();
+		self.set_obj_at_pt(&pp, obj);
+		self.human_pos = pp;
+	}
 	pub fn eq_data(&self, b: &Level) -> bool {
 		self.data == b.data && self.human_pos == b.human_pos
 	}
@@ -803,7 +1212,7 @@ impl Level {
 		( v.0 | v.1 | (self.w as i32 - v.0 - 1) | (self.h as i32 - v.1 - 1)  ) >= 0
 	}
 	pub fn vector_in_bounds8(&self, v: &VectorSm) -> bool {
-		v.0 >= 0 && v.0 < (self.w as i8) && v.1 >= 0 && v.1 < (self.h as i8)
+		v.0 >= 0 && (v.0 as i32) < (self.w as i32) && v.1 >= 0 && (v.1 as i32) < (self.h as i32)
 	}
 	pub fn force_vector_in_bounds(&self, v: &Vector) -> Vector {
 		let mut v = v.clone();
@@ -814,13 +1223,23 @@ impl Level {
 		v
 	}
 	pub fn to_string(&self) -> String {
+		self.to_string_with_format(LevelFormat::Native)
+	}
+	pub fn to_xsb(&self) -> String {
+		self.to_string_with_format(LevelFormat::Xsb)
+	}
+	pub fn to_string_with_format(&self, format: LevelFormat) -> String {
 		let mut s = String::new();
 		for _ in 0..self.w+2 { s+="#"; }
 		s += "\n";
 		for y in 0..self.h as usize {
 			s += "#";
 			for x in 0..self.w as usize {
-				s += &self.get_obj_at_idx(y * self.w as usize + x).to_char().to_string();
+				let obj = self.get_obj_at_idx(y * self.w as usize + x);
+				s.push(match format {
+					LevelFormat::Native => obj.to_char(),
+					LevelFormat::Xsb => obj.to_xsb_char(),
+				});
 			}
 			s += "#\n";
 		}
@@ -830,36 +1249,49 @@ impl Level {
 	}
 	pub fn make_win_data(&mut self) {
 		// we need to cache this part, map out where the holes are
-		let mut blocks: [u64; 4] = [0_u64; 4];
-		let mut data: u64 = 0; 
-		let mut bits_used: usize = 0;
-		let mut block = 0;
-		for o in self.data.iter() {
-			if bits_used % 64 == 0 && bits_used != 0 {
-				blocks[block] = data;
-				block += 1;
-				data = 0;
-			}
-			data = data << 1;
-			data |= (*o==Obj::Hole || *o==Obj::BoxxInHole || *o==Obj::HumanInHole) as u64;
-			bits_used += 1;
-		}
-		// align last block
-		if bits_used % 64 != 0 {
-			data = data << (64 - (bits_used % 64));
-		}
-		blocks[block] = data;
-		self.win_data = blocks;
+		self.win_data = BitBlocks::from_data(&self.data, |o| *o==Obj::Hole || *o==Obj::BoxxInHole || *o==Obj::HumanInHole);
 		if false {
 			println!("win data:");
-			for block in self.win_data.iter() {
-				for i in 0..63 {
-					print!("{}", (block >> (63-i)) & 1);
-				}
-				println!();
-			}
+			self.win_data.print();
 		}
 	}
 }
 
 //unsafe impl Send for Level {};
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn file_string_round_trip() {
+		// keys keep their spelling and order, so the text comes back the same
+		let text = "#######\n#&*O  #\n#  *O #\n#######\n\nTitle: two boxes\nauthor: someone\npath: RdR\n";
+		let level = Level::from_str(text).unwrap();
+		assert_eq!(level.to_file_string(), text);
+		let level2 = Level::from_str(&level.to_file_string()).unwrap();
+		assert!(level2 == level);
+		assert_eq!(level2.get_title_str(), "two boxes");
+
+		// XSB comes back in our own format, but it's the same level
+		let xsb = Level::from_xsb("#######\n#@$.  #\n#  $. #\n#######\nTitle: two boxes\n").unwrap();
+		let xsb2 = Level::from_str(&xsb.to_file_string()).unwrap();
+		assert!(xsb2 == xsb);
+		assert_eq!(xsb2.to_file_string(), xsb.to_file_string());
+	}
+
+	#[test]
+	fn transform_and_back() {
+		let level = Level::from_str("#######\n#&*O  #\n#  *O #\n#######\n\npath: RdR\n").unwrap();
+		assert_eq!(level.path_solves(&level.get_keyval("path")), Ok(true));
+		for sym in ALLSYMMETRIES.iter() {
+			let t = level.transform(*sym);
+			let dims = if sym.swaps_dims() { (level.h, level.w) } else { (level.w, level.h) };
+			assert_eq!((t.w, t.h), dims, "{}", sym);
+			assert_eq!(t.path_solves(&t.get_keyval("path")), Ok(true), "{}", sym);
+			let back = t.transform(sym.inverse());
+			assert_eq!(back.to_file_string(), level.to_file_string(), "{}", sym);
+			assert!(back == level, "{}", sym);
+		}
+	}
+}
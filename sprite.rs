@@ -7,7 +7,6 @@ use wasm_bindgen::prelude::*;
 
 use crate::vector::Vector;
 use super::{Obj,console_log};
-use crate::time::{get_time_ms};
 
 #[wasm_bindgen]
 pub fn tween(x: f64) -> f64 {
@@ -62,9 +61,10 @@ impl Sprite {
 			final_xy,
 		}
 	}
-	pub fn apply_trans(&mut self, trans: Trans) {		
-		if !self.is_moving() { 
-			self.initial_time = get_time_ms();
+	// now is the time in ms, from whatever clock the game is using
+	pub fn apply_trans(&mut self, trans: Trans, now: f64) {		
+		if !self.is_moving(now) { 
+			self.initial_time = now;
 			self.duration = trans.duration;
 			self.initial_xy = trans.initial_xy.clone();
 			self.final_xy = trans.final_xy.clone();
@@ -73,13 +73,19 @@ impl Sprite {
 			console_log("move requested while already moving!");
 		}
 	}
-	pub fn is_moving(&self) -> bool {
-		get_time_ms() < (self.initial_time + self.duration)
+	pub fn set_xy(&mut self, xy: Vector) {
+		// jump straight to a location, cancelling any transition in progress
+		self.initial_xy = xy;
+		self.final_xy = xy;
+		self.duration = 0.0;
+	}
+	pub fn is_moving(&self, now: f64) -> bool {
+		now < (self.initial_time + self.duration)
 	}
 
-	pub fn get_xy(&mut self) -> [f64;2] {
+	pub fn get_xy(&mut self, now: f64) -> [f64;2] {
 		// linear
-		let t = get_time_ms();
+		let t = now;
 		if t <= self.initial_time {
 			return [f64::from(self.initial_xy.0), f64::from(self.initial_xy.1)];
 		} 
@@ -105,8 +111,8 @@ impl Sprite {
 		let ny = tween(delta) * f64::from(self.final_xy.1 - self.initial_xy.1) + f64::from(self.initial_xy.1);
 		[nx,ny]
 	}
-	pub fn get_sprite_info(&mut self) -> SpriteInfo {
-		let pt = self.get_xy();
+	pub fn get_sprite_info(&mut self, now: f64) -> SpriteInfo {
+		let pt = self.get_xy(now);
 		SpriteInfo {
 			id: self.id,
 			obj: self.obj,
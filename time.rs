@@ -1,7 +1,10 @@
 // Box Bopper: Sokoban-like game
 // Copyright David Atkinson 2020-2021
 //
-// time.rs: basic get_time_ms for boxbopper
+// time.rs: basic get_time_ms for boxbopper, and the clocks a Game can run on
+
+use std::rc::Rc;
+use std::cell::Cell;
 
 #[cfg(not(target_arch = "wasm32"))]
 use std::time::{SystemTime,UNIX_EPOCH};
@@ -24,3 +27,42 @@ pub fn get_time_ms() -> f64 {
 	let t = (js_sys::Date::now() as u64 / 10) * 10;
 	return t as f64;
 }
+
+// Where a Game gets the time from, for animating sprites
+pub trait Clock {
+	fn now_ms(&self) -> f64;
+}
+
+// The real time, for animated front ends
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+	fn now_ms(&self) -> f64 {
+		get_time_ms()
+	}
+}
+
+// Time that only moves when told to, so tests and bots get the same result every run
+// Clones share the same time, so keep a clone to advance the clock of a Game that owns the other
+#[derive(Clone)]
+pub struct ManualClock {
+	ms: Rc<Cell<f64>>,
+}
+
+impl ManualClock {
+	pub fn new(start_ms: f64) -> ManualClock {
+		ManualClock { ms: Rc::new(Cell::new(start_ms)) }
+	}
+	pub fn advance(&self, ms: f64) {
+		self.ms.set(self.ms.get() + ms);
+	}
+	pub fn set(&self, ms: f64) {
+		self.ms.set(ms);
+	}
+}
+
+impl Clock for ManualClock {
+	fn now_ms(&self) -> f64 {
+		self.ms.get()
+	}
+}
@@ -9,7 +9,7 @@ use wasm_bindgen::prelude::*;
 use js_sys::Array;
 
 #[wasm_bindgen]
-#[derive(Clone, Copy, PartialEq, Ord, PartialOrd, Eq)]
+#[derive(Clone, Copy, PartialEq, Ord, PartialOrd, Eq, Hash, Debug)]
 pub struct Vector (pub i32, pub i32);
 
 #[derive(Clone, Copy, PartialEq, Ord, PartialOrd, Eq)]
@@ -135,7 +135,7 @@ impl Vector {
 
 
 #[wasm_bindgen]
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 #[repr(u8)]
 pub enum Move { Up=0, Right=1, Down=2, Left=3 }
 
@@ -164,6 +164,16 @@ impl Move {
 			Move::Left  => String::from("L"),
 		}
 	}
+	pub fn from_char(c: &char) -> Option<Move> {
+		// accepts LURD notation, where lower case is a walk and upper case is a push
+		match c {
+			'U' | 'u' => Some(Move::Up),
+			'R' | 'r' => Some(Move::Right),
+			'D' | 'd' => Some(Move::Down),
+			'L' | 'l' => Some(Move::Left),
+			_ => None,
+		}
+	}
 	pub fn from_u32(n: u32) -> Option<Move> {
 		match n {
 			0 => Some(Move::Up),
@@ -218,3 +228,90 @@ impl Move {
 
 pub const ALLMOVES: [Move; 4] = [ Move::Up, Move::Right, Move::Down, Move::Left ];
 
+
+// The eight ways a level can be rotated and / or mirrored
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Symmetry { Identity, RotateCw, Rotate180, RotateCcw, FlipH, FlipV, Transpose, AntiTranspose }
+
+pub const ALLSYMMETRIES: [Symmetry; 8] = [ Symmetry::Identity, Symmetry::RotateCw, Symmetry::Rotate180, Symmetry::RotateCcw,
+										   Symmetry::FlipH, Symmetry::FlipV, Symmetry::Transpose, Symmetry::AntiTranspose ];
+
+impl std::str::FromStr for Symmetry {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Symmetry, String> {
+		match s {
+			"identity"       => Ok(Symmetry::Identity),
+			"rotate_cw"      => Ok(Symmetry::RotateCw),
+			"rotate_180"     => Ok(Symmetry::Rotate180),
+			"rotate_ccw"     => Ok(Symmetry::RotateCcw),
+			"flip_h"         => Ok(Symmetry::FlipH),
+			"flip_v"         => Ok(Symmetry::FlipV),
+			"transpose"      => Ok(Symmetry::Transpose),
+			"anti_transpose" => Ok(Symmetry::AntiTranspose),
+			_ => Err(format!("Unknown symmetry {}", s)),
+		}
+	}
+}
+
+impl std::fmt::Display for Symmetry {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		let s = match self {
+			Symmetry::Identity      => "identity",
+			Symmetry::RotateCw      => "rotate_cw",
+			Symmetry::Rotate180     => "rotate_180",
+			Symmetry::RotateCcw     => "rotate_ccw",
+			Symmetry::FlipH         => "flip_h",
+			Symmetry::FlipV         => "flip_v",
+			Symmetry::Transpose     => "transpose",
+			Symmetry::AntiTranspose => "anti_transpose",
+		};
+		f.pad(s)
+	}
+}
+
+impl Symmetry {
+	pub fn swaps_dims(&self) -> bool {
+		// does width become height (and height become width)?
+		matches!(self, Symmetry::RotateCw | Symmetry::RotateCcw | Symmetry::Transpose | Symmetry::AntiTranspose)
+	}
+	pub fn map_dir(&self, d: &Vector) -> Vector {
+		// maps a direction (no offset needed)
+		match self {
+			Symmetry::Identity      => Vector( d.0,  d.1),
+			Symmetry::RotateCw      => Vector(-d.1,  d.0),
+			Symmetry::Rotate180     => Vector(-d.0, -d.1),
+			Symmetry::RotateCcw     => Vector( d.1, -d.0),
+			Symmetry::FlipH         => Vector(-d.0,  d.1),
+			Symmetry::FlipV         => Vector( d.0, -d.1),
+			Symmetry::Transpose     => Vector( d.1,  d.0),
+			Symmetry::AntiTranspose => Vector(-d.1, -d.0),
+		}
+	}
+	pub fn map_pt(&self, pt: &Vector, w: u16, h: u16) -> Vector {
+		// maps a point in a w x h level to where it ends up in the transformed level
+		let (w, h) = (w as i32, h as i32);
+		let d = self.map_dir(pt);
+		match self {
+			Symmetry::Identity      => d,
+			Symmetry::RotateCw      => d.add(&Vector(h-1, 0)),
+			Symmetry::Rotate180     => d.add(&Vector(w-1, h-1)),
+			Symmetry::RotateCcw     => d.add(&Vector(0, w-1)),
+			Symmetry::FlipH         => d.add(&Vector(w-1, 0)),
+			Symmetry::FlipV         => d.add(&Vector(0, h-1)),
+			Symmetry::Transpose     => d,
+			Symmetry::AntiTranspose => d.add(&Vector(h-1, w-1)),
+		}
+	}
+	pub fn map_move(&self, m: &Move) -> Move {
+		let d = self.map_dir(&m.to_vector());
+		*ALLMOVES.iter().find(|m2| m2.to_vector() == d).unwrap()
+	}
+	pub fn inverse(&self) -> Symmetry {
+		// the symmetry that turns a transformed level back again (only the rotations by 90 degrees aren't their own)
+		match self {
+			Symmetry::RotateCw  => Symmetry::RotateCcw,
+			Symmetry::RotateCcw => Symmetry::RotateCw,
+			s => *s,
+		}
+	}
+}